@@ -1,6 +1,9 @@
-use crate::context::ContextManager;
-use crate::llm::{LLM, Message, Role};
-use crate::session::SessionManager;
+use crate::context::{ContextManager, is_summary};
+use crate::llm::{LLM, Message, Role, estimate_tokens};
+use crate::session::export::ExportFormat;
+use crate::session::import::ImportFormat;
+use crate::session::search::SearchHit;
+use crate::session::{SessionEntry, SessionManager, generate_title};
 use crate::subagent::{SubAgentConfig, SubAgentManager, parse_parallel_tasks};
 use crate::tool::ToolRegistry;
 use anyhow::Result;
@@ -11,10 +14,16 @@ use rustyline::{Cmd, DefaultEditor, EventHandler, KeyEvent};
 use rustyline::error::ReadlineError;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::watch;
 
 const DEFAULT_CMD_HISTORY_FILE_NAME: &str = "__history";
+// Nudge sent when the model stops without a tool call or final answer
+const CONTINUE_PROMPT: &str = "Continue. If finished, wrap the final answer in <final>...</final>.";
+// Messages re-rendered when starting with a resumed conversation
+const RESUME_TAIL_MESSAGES: usize = 6;
+const SEARCH_RESULT_LIMIT: usize = 20;
 
 pub struct Cli {
     context: ContextManager,
@@ -23,6 +32,8 @@ pub struct Cli {
     llm: Arc<dyn LLM>,
     max_loops: usize,
     subagent_manager: SubAgentManager,
+    // Name of the saved session the conversation belongs to, if any
+    current_session: Option<String>,
 }
 
 impl Cli {
@@ -34,7 +45,14 @@ impl Cli {
         max_loops: usize,
     ) -> Self {
         let tool_registry = Arc::new(tool_registry);
-        let subagent_manager = SubAgentManager::new(llm.clone(), tool_registry.clone());
+        let mut subagent_manager = SubAgentManager::new(llm.clone(), tool_registry.clone());
+        // SubAgents summarize with the same model and prompt as the main context
+        if let Some(summarizer) = context.summarizer() {
+            subagent_manager.set_summarizer(summarizer);
+        }
+        if let Some(prompt) = context.summary_prompt() {
+            subagent_manager.set_summary_prompt(prompt);
+        }
 
         Self {
             context,
@@ -43,15 +61,23 @@ impl Cli {
             llm,
             max_loops,
             subagent_manager,
+            current_session: None,
         }
     }
 
+    pub fn set_current_session(&mut self, session_id: Option<String>) {
+        self.current_session = session_id;
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         println!("Mini Agent CLI-Type /help for commands");
+        if let Some(id) = self.session_manager.current_session_id() {
+            println!("Session: {} (autosaved)", id);
+        }
 
         // inject System Prompt
-        let system_prompt = self.tool_registry.generate_system_prompt();
-        self.context.inject_system_prompt(system_prompt);
+        self.refresh_system_prompt();
+        self.print_history_tail(RESUME_TAIL_MESSAGES);
 
         let (ctrlc_tx, mut ctrlc_rx) = watch::channel(0u64);
         let ctrlc_tx_signal = ctrlc_tx.clone();
@@ -88,8 +114,32 @@ impl Cli {
                                 println!("  /save <name> - Save session");
                                 println!("  /load <name> - Load session");
                                 println!("  /list - List sessions");
+                                println!("  /search <query> - Search all sessions");
+                                println!("  /rename <old> <new> - Rename a session");
+                                println!("  /delete <name> - Delete a session");
+                                println!(
+                                    "  /export <name> <md|html|jsonl> [path] - Export a session"
+                                );
+                                println!(
+                                    "  /import <path> <name> [openai|anthropic|sharegpt] - Import a conversation"
+                                );
+                                println!(
+                                    "  /rewind <n> - Go back to before the n-th most recent user turn"
+                                );
+                                println!(
+                                    "  /fork <name> - Branch the conversation into a new session"
+                                );
+                                println!(
+                                    "  /branches - Show the branch tree of the current session"
+                                );
+                                println!("  /recover [id] - List or restore autosaved transcripts");
                                 println!("  /clear - Clear context");
                                 println!("  /tools - List tools");
+                                println!("  /context - Show context token usage");
+                                println!(
+                                    "  /pin [n] - Pin the n-th most recent message (default 1), or list pins"
+                                );
+                                println!("  /unpin <n> - Unpin the n-th most recent message");
                                 println!("  /quit - Exit");
                             }
                             Some("/save") => {
@@ -101,43 +151,259 @@ impl Cli {
                                         println!("Error saving session:{}", e);
                                     } else {
                                         println!("Session saved as:{}", parts[1]);
+                                        self.current_session = Some(parts[1].to_string());
+                                        self.ensure_session_title(parts[1]).await;
                                     }
                                 } else {
                                     println!("Usage: /save <name>");
                                 }
                             }
                             Some("/load") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() > 1 {
+                                    self.load_session(parts[1]);
+                                } else {
+                                    println!("Usage: /load <name>");
+                                }
+                            }
+                            Some("/list") => match self.session_manager.list_recent_sessions() {
+                                Ok(sessions) => print_session_table(&sessions),
+                                Err(e) => println!("Error listing sessions: {}", e),
+                            },
+                            Some("/search") => {
+                                let query = input["/search".len()..].trim();
+                                if query.is_empty() {
+                                    println!("Usage: /search <query>");
+                                } else {
+                                    match self.session_manager.search(query, SEARCH_RESULT_LIMIT) {
+                                        Ok(hits) => match pick_search_hit(&hits) {
+                                            Ok(Some(id)) => {
+                                                self.load_session(&id);
+                                                self.print_history_tail(RESUME_TAIL_MESSAGES);
+                                            }
+                                            Ok(None) => {}
+                                            Err(e) => println!("Error: {}", e),
+                                        },
+                                        Err(e) => println!("Error searching sessions: {}", e),
+                                    }
+                                }
+                            }
+                            Some("/rename") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() == 3 {
+                                    match self.session_manager.rename_session(parts[1], parts[2]) {
+                                        Ok(()) => {
+                                            println!("Renamed {} to {}", parts[1], parts[2]);
+                                            if self.current_session.as_deref() == Some(parts[1]) {
+                                                self.current_session = Some(parts[2].to_string());
+                                            }
+                                            if self.session_manager.current_session_id()
+                                                == Some(parts[2])
+                                                && let Some(store) =
+                                                    self.tool_registry.spill_store()
+                                                && let Err(e) = store.set_dir(
+                                                    self.session_manager.spill_dir(parts[2]),
+                                                )
+                                            {
+                                                error!("{}", e);
+                                            }
+                                        }
+                                        Err(e) => println!("Error renaming session: {}", e),
+                                    }
+                                } else {
+                                    println!("Usage: /rename <old> <new>");
+                                }
+                            }
+                            Some("/delete") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() != 2 {
+                                    println!("Usage: /delete <name>");
+                                } else if confirm(&format!("Delete session '{}'?", parts[1])) {
+                                    match self.session_manager.delete_session(parts[1]) {
+                                        Ok(()) => {
+                                            println!("Deleted {}", parts[1]);
+                                            if self.current_session.as_deref() == Some(parts[1]) {
+                                                self.current_session = None;
+                                            }
+                                        }
+                                        Err(e) => println!("Error deleting session: {}", e),
+                                    }
+                                }
+                            }
+                            Some("/import") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() < 3 {
+                                    println!(
+                                        "Usage: /import <path> <name> [openai|anthropic|sharegpt]"
+                                    );
+                                } else {
+                                    let imported = parts
+                                        .get(3)
+                                        .map(|f| ImportFormat::parse(f))
+                                        .transpose()
+                                        .and_then(|format| {
+                                            self.session_manager.import_session(
+                                                Path::new(parts[1]),
+                                                parts[2],
+                                                format,
+                                            )
+                                        });
+                                    match imported {
+                                        Ok(ids) => println!(
+                                            "Imported as {}. Use /load to continue it",
+                                            ids.join(", ")
+                                        ),
+                                        Err(e) => println!("Error importing: {}", e),
+                                    }
+                                }
+                            }
+                            Some("/export") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() < 3 {
+                                    println!("Usage: /export <name> <md|html|jsonl> [path]");
+                                } else {
+                                    match ExportFormat::parse(parts[2]) {
+                                        Ok(format) => {
+                                            let path = parts
+                                                .get(3)
+                                                .map(PathBuf::from)
+                                                .unwrap_or_else(|| {
+                                                    PathBuf::from(format!(
+                                                        "{}.{}",
+                                                        parts[1],
+                                                        format.extension()
+                                                    ))
+                                                });
+                                            match self
+                                                .session_manager
+                                                .export_session(parts[1], format, &path)
+                                            {
+                                                Ok(()) => {
+                                                    println!("Exported to {}", path.display())
+                                                }
+                                                Err(e) => {
+                                                    println!("Error exporting session: {}", e)
+                                                }
+                                            }
+                                        }
+                                        Err(e) => println!("Error: {}", e),
+                                    }
+                                }
+                            }
+                            Some("/rewind") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                match parts.get(1).map(|n| n.parse::<usize>()) {
+                                    Some(Ok(n)) if n >= 1 => self.rewind(n),
+                                    _ => println!("Usage: /rewind <n>"),
+                                }
+                            }
+                            Some("/fork") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() > 1 {
+                                    self.fork(parts[1]);
+                                } else {
+                                    println!("Usage: /fork <name>");
+                                }
+                            }
+                            Some("/branches") => match &self.current_session {
+                                Some(id) => match self.session_manager.list_branches(id) {
+                                    Ok(nodes) => {
+                                        for node in nodes {
+                                            println!(
+                                                "{}{} {}{} ({} messages{}){}",
+                                                "  ".repeat(node.depth),
+                                                if node.depth > 0 { "└─" } else { "*" },
+                                                node.id,
+                                                node.title
+                                                    .map(|t| format!(" \"{}\"", t))
+                                                    .unwrap_or_default(),
+                                                node.messages,
+                                                node.branch_point
+                                                    .map(|p| format!(", branched at {}", p))
+                                                    .unwrap_or_default(),
+                                                if &node.id == id { "  <- current" } else { "" }
+                                            );
+                                        }
+                                    }
+                                    Err(e) => println!("Error listing branches: {}", e),
+                                },
+                                None => {
+                                    println!("Not in a saved session. Use /save or /load first")
+                                }
+                            },
+                            Some("/recover") => {
                                 let parts: Vec<&str> = input.split_whitespace().collect();
                                 if parts.len() > 1 {
                                     if let Err(e) = self
                                         .session_manager
-                                        .load_session(parts[1], &mut self.context)
+                                        .recover_transcript(parts[1], &mut self.context)
                                     {
-                                        println!("Error loading session: {}", e);
+                                        println!("Error recovering session: {}", e);
                                     } else {
-                                        println!("Session loaded");
-                                        // Re-inject system prompt after loading to ensure tools are current
-                                        let system_prompt =
-                                            self.tool_registry.generate_system_prompt();
-                                        self.context.inject_system_prompt(system_prompt);
+                                        println!("Session recovered");
+                                        self.current_session = None;
+                                        self.switch_session_files(parts[1]);
+                                        self.refresh_system_prompt();
+                                        self.autosave();
                                     }
                                 } else {
-                                    println!("Usage: /load <name>");
+                                    match self.session_manager.list_transcripts() {
+                                        Ok(transcripts) if transcripts.is_empty() => {
+                                            println!("No autosaved transcripts")
+                                        }
+                                        Ok(transcripts) => {
+                                            for t in transcripts {
+                                                println!(
+                                                    "  {}  {}  {} messages",
+                                                    t.id,
+                                                    t.updated_display(),
+                                                    t.messages
+                                                );
+                                            }
+                                        }
+                                        Err(e) => println!("Error listing transcripts: {}", e),
+                                    }
                                 }
                             }
-                            Some("/list") => match self.session_manager.list_sessions() {
-                                Ok(sessions) => {
-                                    println!("Sessions: {:?}", sessions);
-                                }
-                                Err(e) => println!("Error listing sessions: {}", e),
-                            },
                             Some("/clear") => {
                                 self.context.clear_history();
+                                // Keep the cleared messages out of a recovered transcript
+                                if let Err(e) = self.session_manager.record_rewind(&self.context) {
+                                    error!("Session autosave failed: {}", e);
+                                }
                                 // Re-inject system prompt
-                                let system_prompt = self.tool_registry.generate_system_prompt();
-                                self.context.inject_system_prompt(system_prompt);
+                                self.refresh_system_prompt();
                                 println!("Context cleared");
                             }
+                            Some("/pin") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() > 1 {
+                                    self.set_pinned(parts[1], true);
+                                } else {
+                                    let pinned = self.context.pinned_messages();
+                                    if pinned.is_empty() {
+                                        println!("No pinned messages. Usage: /pin <n>");
+                                    }
+                                    let len = self.context.get_history().len();
+                                    for (index, message) in pinned {
+                                        println!(
+                                            "  [{}] {:?}: {}",
+                                            len - index,
+                                            message.role,
+                                            preview(&message.content, 60)
+                                        );
+                                    }
+                                }
+                            }
+                            Some("/unpin") => {
+                                let parts: Vec<&str> = input.split_whitespace().collect();
+                                if parts.len() > 1 {
+                                    self.set_pinned(parts[1], false);
+                                } else {
+                                    println!("Usage: /unpin <n>");
+                                }
+                            }
+                            Some("/context") => self.print_context_report(),
                             Some("/tools") => {
                                 for tool in self.tool_registry.list() {
                                     println!("- {}: {}", tool.name(), tool.description());
@@ -150,10 +416,9 @@ impl Cli {
 
                     // Chat with LLM
                     let _ = ctrlc_rx.borrow_and_update();
-                    self.context.add_message(Message {
-                        role: Role::User,
-                        content: input.to_string(),
-                    });
+                    self.add_message(Message::new(Role::User, input.to_string()));
+                    // Pick the memories relevant to the new request
+                    self.refresh_system_prompt();
 
                     let mut agent_loop_count = 0;
 
@@ -178,10 +443,7 @@ impl Cli {
                         };
 
                         print_formatted_response(&response);
-                        self.context.add_message(Message {
-                            role: Role::Assistant,
-                            content: response.clone(),
-                        });
+                        self.add_message(Message::new(Role::Assistant, response.clone()));
 
                         if let Some(tool_call) = parse_tool_call(&response) {
                             println!(">> Executing tool: {}...", &tool_call.name);
@@ -223,44 +485,44 @@ impl Cli {
                             };
 
                             println!(">> Tool Output:\n{}", output.trim());
-                            self.context.add_message(Message {
-                                role: Role::User, // Treating tool output as User message for simplicity/compatibility
-                                content: format!("Tool '{}' output:\n{}", tool_call.name, output),
-                            });
+                            let output = self.tool_registry.limit_output(&tool_call.name, output);
+                            self.add_message(Message::tool(&tool_call.name, output));
+
+                            // Refresh the system prompt so memory changes are visible
+                            if tool_call.name == "memory" {
+                                self.refresh_system_prompt();
+                            }
 
                             // Check for parallel tasks
                             if let Some(configs) = parse_parallel_tasks(&response) {
                                 info!("Found {} parallel tasks", configs.len());
                                 let results = self.handle_parallel_tasks(configs, &ctrlc_rx).await;
-                                self.context.add_message(Message {
-                                    role: Role::User,
-                                    content: format!(
-                                        "Parallel tasks results:\n{}",
-                                        results.join("\n---\n")
-                                    ),
-                                });
+                                self.add_message(Message::tool(
+                                    "parallel",
+                                    format!("Parallel tasks results:\n{}", results.join("\n---\n")),
+                                ));
                             }
 
                             // Continue loop
                         } else if let Some(final_text) = extract_final(&response) {
-                            self.context.add_message(Message {
-                                role: Role::Assistant,
-                                content: final_text,
-                            });
+                            self.add_message(Message::final_answer(final_text));
                             break;
                         } else {
-                            self.context.add_message(Message {
-                                role: Role::User,
-                                content: "Continue. If finished, wrap the final answer in <final>...</final>."
-                                    .to_string(),
-                            });
+                            self.add_message(Message::new(Role::User, CONTINUE_PROMPT));
                         }
                     }
 
                     // Try to compress context
+                    let compressions = self.context.compression_history().len();
                     if let Err(e) = self.context.compress().await {
                         println!("(Context compression error: {})", e);
                     }
+                    if self.context.compression_history().len() > compressions
+                        && let Err(e) = self.session_manager.record_compression(&self.context)
+                    {
+                        error!("Session autosave failed: {}", e);
+                    }
+                    self.autosave();
                 }
                 Err(ReadlineError::Interrupted) => {
                     println!("CTRL-C");
@@ -283,6 +545,288 @@ impl Cli {
         Ok(())
     }
 
+    // Go back to just before the n-th most recent user turn. When the
+    // conversation is a saved session, the current continuation stays in that
+    // session and the rewound conversation continues as a new branch.
+    fn rewind(&mut self, n: usize) {
+        let turns: Vec<usize> = self
+            .context
+            .get_history()
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.role == Role::User && m.content != CONTINUE_PROMPT)
+            .map(|(i, _)| i)
+            .collect();
+        if n > turns.len() {
+            println!("Only {} user turns in context", turns.len());
+            return;
+        }
+        let index = turns[turns.len() - n];
+        let removed = self.context.get_history()[index].content.clone();
+
+        if let Some(current) = self.current_session.clone() {
+            if let Err(e) = self.session_manager.save_session(&current, &self.context) {
+                println!("Error saving session before rewind: {}", e);
+                return;
+            }
+            self.context.truncate(index);
+            let branch = self.session_manager.next_branch_id(&current);
+            match self
+                .session_manager
+                .fork_session(&branch, Some(&current), &self.context)
+            {
+                Ok(()) => {
+                    println!(
+                        "Previous continuation kept in '{}'; now on branch '{}'",
+                        current, branch
+                    );
+                    self.switch_session_files(&branch);
+                    self.current_session = Some(branch);
+                }
+                Err(e) => println!("Error creating branch: {}", e),
+            }
+        } else {
+            self.context.truncate(index);
+        }
+        if let Err(e) = self.session_manager.record_rewind(&self.context) {
+            error!("Session autosave failed: {}", e);
+        }
+        println!("Rewound to before: {}", preview(&removed, 60));
+    }
+
+    // Save the conversation as a new session branching off the current one
+    fn fork(&mut self, name: &str) {
+        let parent = self.current_session.clone();
+        if let Some(parent) = &parent
+            && let Err(e) = self.session_manager.save_session(parent, &self.context)
+        {
+            println!("Error saving session before fork: {}", e);
+            return;
+        }
+        match self
+            .session_manager
+            .fork_session(name, parent.as_deref(), &self.context)
+        {
+            Ok(()) => {
+                match &parent {
+                    Some(parent) => println!("Forked '{}' into '{}'", parent, name),
+                    None => println!("Forked into '{}'", name),
+                }
+                self.current_session = Some(name.to_string());
+                self.switch_session_files(name);
+            }
+            Err(e) => println!("Error forking session: {}", e),
+        }
+    }
+
+    fn load_session(&mut self, session_id: &str) {
+        if let Err(e) = self
+            .session_manager
+            .load_session(session_id, &mut self.context)
+        {
+            println!("Error loading session: {}", e);
+        } else {
+            println!("Session loaded");
+            self.current_session = Some(session_id.to_string());
+            self.switch_session_files(session_id);
+            // Re-inject system prompt after loading to ensure tools are current
+            self.refresh_system_prompt();
+        }
+    }
+
+    // Continue autosaving into the transcript of a loaded session and read
+    // its spilled tool outputs, so the loaded history is not written twice
+    // and its output handles keep resolving
+    fn switch_session_files(&mut self, session_id: &str) {
+        if self.session_manager.current_session_id().is_some()
+            && let Err(e) = self.session_manager.start_autosave(Some(session_id))
+        {
+            error!("Session autosave failed: {}", e);
+        }
+        if let Some(store) = self.tool_registry.spill_store()
+            && let Err(e) = store.set_dir(self.session_manager.spill_dir(session_id))
+        {
+            error!("{}", e);
+        }
+    }
+
+    // Render the system prompt for the latest user request, so the memories
+    // injected into it follow the task
+    fn refresh_system_prompt(&mut self) {
+        let task = self
+            .context
+            .get_history()
+            .iter()
+            .rev()
+            .find(|m| m.role == Role::User && m.content != CONTINUE_PROMPT)
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+        let system_prompt = self.tool_registry.generate_system_prompt(&task);
+        self.context.inject_system_prompt(system_prompt);
+    }
+
+    // Re-render the last messages of a resumed conversation
+    fn print_history_tail(&self, n: usize) {
+        let messages: Vec<&Message> = self
+            .context
+            .get_history()
+            .iter()
+            .filter(|m| m.role != Role::System)
+            .collect();
+        if messages.is_empty() {
+            return;
+        }
+        let skipped = messages.len().saturating_sub(n);
+        if skipped > 0 {
+            println!("({} earlier messages)", skipped);
+        }
+        for m in &messages[skipped..] {
+            match m.role {
+                Role::User => println!(">> {}\n", m.content),
+                Role::Assistant => print_formatted_response(&m.content),
+                Role::Tool => println!(
+                    ">> Tool Output ({}): {}\n",
+                    m.metadata.tool_name.as_deref().unwrap_or("tool"),
+                    preview(&m.content, 80)
+                ),
+                Role::System => {}
+            }
+        }
+    }
+
+    // Give a saved session an LLM-generated title if it has none yet
+    async fn ensure_session_title(&self, session_id: &str) {
+        if self.session_manager.session_title(session_id).is_some() {
+            return;
+        }
+        match generate_title(self.llm.as_ref(), self.context.get_history()).await {
+            Ok(title) => {
+                if let Err(e) = self.session_manager.set_session_title(session_id, &title) {
+                    error!("Failed to store session title: {}", e);
+                } else {
+                    println!("Title: {}", title);
+                }
+            }
+            Err(e) => debug!("Session title generation skipped: {}", e),
+        }
+    }
+
+    // Add a message to the context and append it to the session transcript
+    fn add_message(&mut self, message: Message) {
+        self.context.add_message(message);
+        self.autosave();
+    }
+
+    fn autosave(&mut self) {
+        if let Err(e) = self.session_manager.autosave(&self.context) {
+            error!("Session autosave failed: {}", e);
+        }
+    }
+
+    // Show how the context budget is spent, using the estimator compress() uses
+    fn print_context_report(&self) {
+        let history = self.context.get_history();
+        let total = self.context.total_tokens();
+        let max = self.context.max_tokens();
+        println!(
+            "Context: {} / {} tokens ({}%), {} messages",
+            total,
+            max,
+            total * 100 / max.max(1),
+            history.len()
+        );
+
+        let mut by_kind: Vec<(&str, usize, usize)> = Vec::new();
+        let mut add = |kind, tokens| match by_kind.iter_mut().find(|(k, _, _)| *k == kind) {
+            Some(entry) => {
+                entry.1 += 1;
+                entry.2 += tokens;
+            }
+            None => by_kind.push((kind, 1, tokens)),
+        };
+        for (i, m) in history.iter().enumerate() {
+            let tokens = estimate_tokens(&m.content);
+            match m.role {
+                Role::System if i == 0 && !is_summary(m) => add("system prompt", tokens),
+                Role::System if is_summary(m) => add("summary", tokens),
+                Role::System => add("system", tokens),
+                Role::User => add("user", tokens),
+                Role::Assistant => add("assistant", tokens),
+                Role::Tool => add("tool", tokens),
+            }
+        }
+        for (kind, count, tokens) in &by_kind {
+            println!("  {:<14} {:>3} msg {:>7} tokens", kind, count, tokens);
+            if *kind == "system prompt" {
+                let instructions =
+                    estimate_tokens(&self.tool_registry.generate_tool_instructions());
+                println!("    {:<22} {:>7} tokens", "tool instructions", instructions);
+            }
+        }
+
+        println!("Messages (n as used by /pin, * = pinned):");
+        for (i, m) in history.iter().enumerate() {
+            let role = match (&m.role, &m.metadata.tool_name) {
+                (Role::Tool, Some(name)) => format!("tool:{}", name),
+                _ => format!("{:?}", m.role).to_lowercase(),
+            };
+            println!(
+                "  {:>4}{} {:<16} {:>7}  {}",
+                history.len() - i,
+                if m.metadata.pinned { "*" } else { " " },
+                role,
+                estimate_tokens(&m.content),
+                preview(&m.content, 50)
+            );
+        }
+
+        let compressions = self.context.compression_history();
+        if compressions.is_empty() {
+            println!("No compressions yet");
+        } else {
+            println!("Compressions:");
+            for c in compressions {
+                println!(
+                    "  {} {} -> {} tokens, {} messages summarized, {} pinned kept",
+                    c.timestamp,
+                    c.tokens_before,
+                    c.tokens_after,
+                    c.messages_summarized,
+                    c.messages_pinned
+                );
+            }
+        }
+    }
+
+    // Pin or unpin a message, counted back from the most recent one (1 = last)
+    fn set_pinned(&mut self, arg: &str, pinned: bool) {
+        let len = self.context.get_history().len();
+        let index = match arg.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= len => len - n,
+            _ => {
+                println!("Invalid message number: {} (1-{})", arg, len);
+                return;
+            }
+        };
+        let res = if pinned {
+            self.context.pin(index)
+        } else {
+            self.context.unpin(index)
+        };
+        match res {
+            Ok(()) => {
+                let message = &self.context.get_history()[index];
+                println!(
+                    "{} {:?}: {}",
+                    if pinned { "Pinned" } else { "Unpinned" },
+                    message.role,
+                    preview(&message.content, 60)
+                );
+            }
+            Err(e) => println!("Error: {}", e),
+        }
+    }
+
     // Handle subagent tool calls
     async fn handle_subagent_call(
         &mut self,
@@ -459,6 +1003,134 @@ fn print_formatted_response(response: &str) {
     println!(); // Newline
 }
 
+pub fn print_session_table(sessions: &[SessionEntry]) {
+    if sessions.is_empty() {
+        println!("No sessions");
+        return;
+    }
+    println!(
+        "{:<28} {:<32} {:<16} {:<20} {:>5} {:>7}  CWD",
+        "ID", "TITLE", "UPDATED", "MODEL", "MSGS", "TOKENS"
+    );
+    for s in sessions {
+        let title = match (&s.title, s.autosaved) {
+            (Some(title), _) => preview(title, 30),
+            (None, true) => "(autosaved)".to_string(),
+            (None, false) => "-".to_string(),
+        };
+        let model = match (s.provider.as_str(), s.model.as_str()) {
+            ("", "") => "-".to_string(),
+            (provider, model) => preview(&format!("{}/{}", provider, model), 18),
+        };
+        println!(
+            "{:<28} {:<32} {:<16} {:<20} {:>5} {:>7}  {}",
+            s.id,
+            title,
+            s.updated_display(),
+            model,
+            s.messages,
+            s.tokens,
+            if s.cwd.is_empty() { "-" } else { &s.cwd }
+        );
+    }
+}
+
+// Let the user choose a session to resume from the terminal
+pub fn pick_session(sessions: &[SessionEntry]) -> Result<Option<String>> {
+    if sessions.is_empty() {
+        println!("No sessions to resume");
+        return Ok(None);
+    }
+    println!("Sessions (most recent first):");
+    for (i, s) in sessions.iter().enumerate() {
+        println!(
+            "  {:>3}. {}  {}  {} messages{}",
+            i + 1,
+            s.id,
+            s.updated_display(),
+            s.messages,
+            if s.autosaved { " (autosaved)" } else { "" }
+        );
+    }
+    print!(
+        "Select a session [1-{}] (empty to start fresh): ",
+        sessions.len()
+    );
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match line.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= sessions.len() => Ok(Some(sessions[n - 1].id.clone())),
+        _ => Err(anyhow::anyhow!("Invalid selection: {}", line)),
+    }
+}
+
+pub fn print_search_hits(hits: &[SearchHit]) {
+    for (i, hit) in hits.iter().enumerate() {
+        let title = hit
+            .title
+            .as_deref()
+            .map(|t| format!(" ({})", preview(t, 40)))
+            .unwrap_or_default();
+        println!(
+            "  {:>3}. {}{} turn {} [{:?}]",
+            i + 1,
+            hit.session_id.cyan(),
+            title,
+            hit.turn + 1,
+            hit.role
+        );
+        println!("       {}", hit.snippet);
+    }
+}
+
+// Show search results and let the user jump into one of the sessions
+fn pick_search_hit(hits: &[SearchHit]) -> Result<Option<String>> {
+    if hits.is_empty() {
+        println!("No matches");
+        return Ok(None);
+    }
+    print_search_hits(hits);
+    print!("Open result [1-{}] (empty to stay): ", hits.len());
+    std::io::Write::flush(&mut std::io::stdout())?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match line.parse::<usize>() {
+        Ok(n) if n >= 1 && n <= hits.len() => Ok(Some(hits[n - 1].session_id.clone())),
+        _ => Err(anyhow::anyhow!("Invalid selection: {}", line)),
+    }
+}
+
+// Ask a yes/no question on the terminal; anything but "y" means no
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N]: ", question);
+    if std::io::Write::flush(&mut std::io::stdout()).is_err() {
+        return false;
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line).is_ok()
+        && matches!(line.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+// First line of a message, shortened for one-line listings
+fn preview(content: &str, max_chars: usize) -> String {
+    let line = content.lines().next().unwrap_or("");
+    if line.chars().count() > max_chars || content.lines().nth(1).is_some() {
+        let short: String = line.chars().take(max_chars).collect();
+        format!("{}...", short)
+    } else {
+        line.to_string()
+    }
+}
+
 fn extract_final(content: &str) -> Option<String> {
     let re = Regex::new(r"(?s)<final>\s*(.*?)\s*</final>").ok()?;
     let caps = re.captures(content)?;
@@ -502,6 +1174,59 @@ fn parse_tool_call(content: &str) -> Option<ToolCall> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::session::storage::DirectoryStore;
+    use crate::session::transcript::{read_transcript, transcript_path};
+
+    struct EchoLLM;
+
+    #[async_trait::async_trait]
+    impl LLM for EchoLLM {
+        async fn complete(&self, _messages: &[Message]) -> Result<String> {
+            Ok("<final>ok</final>".to_string())
+        }
+    }
+
+    fn test_cli(dir: &Path) -> Cli {
+        let store = Box::new(DirectoryStore::new(dir.to_path_buf(), 0));
+        let mut session_manager = SessionManager::new(dir.to_path_buf(), store);
+        session_manager.start_autosave(None).unwrap();
+        Cli::new(
+            ContextManager::new(1000),
+            session_manager,
+            ToolRegistry::new(),
+            Arc::new(EchoLLM),
+            1,
+        )
+    }
+
+    #[test]
+    fn test_fork_and_rewind_autosave_into_branch() {
+        let dir = tempfile::tempdir().unwrap();
+        let transcript = |id: &str| {
+            let path = transcript_path(&dir.path().join("transcripts"), id);
+            read_transcript(&path, None)
+                .map(|messages| messages.into_iter().map(|m| m.content).collect::<Vec<_>>())
+                .unwrap_or_default()
+        };
+        let mut cli = test_cli(dir.path());
+        let autosave_id = cli
+            .session_manager
+            .current_session_id()
+            .unwrap()
+            .to_string();
+        cli.add_message(Message::new(Role::User, "first"));
+        cli.fork("main");
+        cli.add_message(Message::new(Role::User, "second"));
+        assert_eq!(transcript(&autosave_id), ["first"]);
+        assert_eq!(transcript("main"), ["first", "second"]);
+
+        // The rewound conversation continues in the branch, the parent keeps its messages
+        cli.rewind(1);
+        cli.add_message(Message::new(Role::User, "instead"));
+        assert_eq!(transcript("main"), ["first", "second"]);
+        assert_eq!(transcript("main-1"), ["first", "instead"]);
+        assert_eq!(cli.current_session.as_deref(), Some("main-1"));
+    }
 
     #[test]
     fn test_parse_tool_call() {
@@ -1,11 +1,39 @@
-use crate::llm::{LLM, Message, Role};
+use crate::llm::{LLM, Message, Role, estimate_tokens};
 use anyhow::Result;
+use log::{debug, warn};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 
+// Tool outputs shorter than this are cheaper to repeat than to deduplicate
+const MIN_DEDUP_CHARS: usize = 200;
+// Tool call arguments quoted in a deduplicated output's note
+const MAX_CALL_ARGS_CHARS: usize = 200;
+
+// One compression pass, for inspecting why and when the context shrank
+#[derive(Debug, Clone)]
+pub struct CompressionRecord {
+    pub timestamp: String,
+    pub tokens_before: usize,
+    pub tokens_after: usize,
+    pub messages_summarized: usize,
+    pub messages_pinned: usize,
+}
+
 pub struct ContextManager {
     history: Vec<Message>,
     max_tokens: usize,
     llm: Option<Arc<dyn LLM>>,
+    // Optional cheaper model used for summaries; falls back to `llm` on failure
+    summarizer: Option<Arc<dyn LLM>>,
+    // Custom summary instructions replacing the default structured prompt
+    summary_prompt: Option<String>,
+    // Content hash of tool outputs -> id of the latest message carrying it
+    tool_output_index: HashMap<u64, String>,
+    compressions: Vec<CompressionRecord>,
 }
 
 impl ContextManager {
@@ -14,6 +42,10 @@ impl ContextManager {
             history: Vec::new(),
             max_tokens,
             llm: None,
+            summarizer: None,
+            summary_prompt: None,
+            tool_output_index: HashMap::new(),
+            compressions: Vec::new(),
         }
     }
 
@@ -21,7 +53,24 @@ impl ContextManager {
         self.llm = Some(llm);
     }
 
-    pub fn add_message(&mut self, message: Message) {
+    pub fn set_summarizer(&mut self, summarizer: Arc<dyn LLM>) {
+        self.summarizer = Some(summarizer);
+    }
+
+    pub fn summarizer(&self) -> Option<Arc<dyn LLM>> {
+        self.summarizer.clone()
+    }
+
+    pub fn set_summary_prompt(&mut self, prompt: String) {
+        self.summary_prompt = Some(prompt);
+    }
+
+    pub fn summary_prompt(&self) -> Option<String> {
+        self.summary_prompt.clone()
+    }
+
+    pub fn add_message(&mut self, mut message: Message) {
+        self.dedup_tool_output(&mut message);
         self.history.push(message);
     }
 
@@ -29,12 +78,127 @@ impl ContextManager {
         &self.history
     }
 
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    // Estimated size of the whole history, as compared against max_tokens
+    pub fn total_tokens(&self) -> usize {
+        self.history
+            .iter()
+            .map(|m| estimate_tokens(&m.content))
+            .sum()
+    }
+
+    pub fn compression_history(&self) -> &[CompressionRecord] {
+        &self.compressions
+    }
+
     pub fn clear_history(&mut self) {
         self.history.clear();
+        self.tool_output_index.clear();
     }
 
     pub fn load_history(&mut self, history: Vec<Message>) {
         self.history = history;
+        self.tool_output_index.clear();
+        for m in &self.history {
+            if let Some(hash) = tool_output_hash(m) {
+                self.tool_output_index
+                    .entry(hash)
+                    .or_insert_with(|| m.metadata.id.clone());
+            }
+        }
+    }
+
+    // Drop every message from `len` on, e.g. to rewind to an earlier turn
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.history.len() {
+            let history = self.history[..len].to_vec();
+            self.load_history(history);
+        }
+    }
+
+    // When a tool output repeats one still in context, the new copy is
+    // replaced with a short note naming the earlier call that produced it.
+    // Earlier messages are left alone, so the transcript stays accurate.
+    fn dedup_tool_output(&mut self, message: &mut Message) {
+        let Some(hash) = tool_output_hash(message) else {
+            return;
+        };
+        let tool_name = message
+            .metadata
+            .tool_name
+            .clone()
+            .unwrap_or_else(|| "tool".to_string());
+        let Some(index) = self.tool_output_index.get(&hash).and_then(|id| {
+            self.history
+                .iter()
+                .rposition(|m| &m.metadata.id == id && m.content == message.content)
+        }) else {
+            self.tool_output_index
+                .insert(hash, message.metadata.id.clone());
+            return;
+        };
+        debug!(
+            "Deduplicated tool output {} ({} chars)",
+            message.metadata.id,
+            message.content.len()
+        );
+        let call = match self.tool_call_args(index, &tool_name) {
+            Some(args) => format!(" of the call with args {}", args),
+            None => String::new(),
+        };
+        message.set_content(format!(
+            "[Duplicate output omitted: identical to the earlier '{}' output{} in this conversation]",
+            tool_name, call
+        ));
+    }
+
+    // Arguments of the `tool_name` call answered by the tool output at
+    // `index`, as written in the assistant message before it
+    fn tool_call_args(&self, index: usize, tool_name: &str) -> Option<String> {
+        let call = self.history[..index]
+            .iter()
+            .rev()
+            .take_while(|m| m.role != Role::User)
+            .find(|m| m.role == Role::Assistant)?;
+        let re = Regex::new(r"(?s)<tool_code>\s*(.*?)\s*</tool_code>").unwrap();
+        let json: Value = serde_json::from_str(&re.captures(&call.content)?[1]).ok()?;
+        if json.get("name")?.as_str()? != tool_name {
+            return None;
+        }
+        let args = json.get("args")?.to_string();
+        Some(match args.char_indices().nth(MAX_CALL_ARGS_CHARS) {
+            Some((i, _)) => format!("{}...", &args[..i]),
+            None => args,
+        })
+    }
+
+    pub fn pin(&mut self, index: usize) -> Result<()> {
+        self.set_pinned(index, true)
+    }
+
+    pub fn unpin(&mut self, index: usize) -> Result<()> {
+        self.set_pinned(index, false)
+    }
+
+    // Pinned messages with their index in the history
+    pub fn pinned_messages(&self) -> Vec<(usize, &Message)> {
+        self.history
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.metadata.pinned)
+            .collect()
+    }
+
+    fn set_pinned(&mut self, index: usize, pinned: bool) -> Result<()> {
+        let len = self.history.len();
+        let message = self.history.get_mut(index).ok_or_else(|| {
+            anyhow::anyhow!("No message at index {} (history has {})", index, len)
+        })?;
+        message.metadata.pinned = pinned;
+        Ok(())
     }
 
     pub fn inject_system_prompt(&mut self, prompt: String) {
@@ -42,29 +206,23 @@ impl ContextManager {
             role: Role::System, ..
         }) = self.history.first()
         {
-            self.history[0].content = prompt;
+            self.history[0].set_content(prompt);
             return;
         }
-        self.history.insert(
-            0,
-            Message {
-                role: Role::System,
-                content: prompt,
-            },
-        );
+        self.history.insert(0, Message::new(Role::System, prompt));
     }
 
     pub async fn compress(&mut self) -> Result<()> {
-        let current_len: usize = self.history.iter().map(|m| m.content.len()).sum();
+        let current_tokens = self.total_tokens();
 
-        // Approx 4 chars per token. If context exceeds limit, compress.
-        if current_len / 4 > self.max_tokens {
+        // If context exceeds limit, compress.
+        if current_tokens > self.max_tokens {
             if self.history.len() <= 5 {
                 return Ok(());
             }
 
             let system_msg = if let Some(first) = self.history.first() {
-                if first.role == Role::System {
+                if first.role == Role::System && !is_summary(first) {
                     Some(first.clone())
                 } else {
                     None
@@ -74,58 +232,168 @@ impl ContextManager {
             };
 
             let last_n = 4;
-            let start_idx = if system_msg.is_some() { 1 } else { 0 };
+            let mut start_idx = if system_msg.is_some() { 1 } else { 0 };
             let end_idx = self.history.len().saturating_sub(last_n);
 
+            // A summary left by a previous pass is folded into the new one
+            // instead of being summarized as ordinary conversation.
+            let previous_summary = match self.history.get(start_idx) {
+                Some(m) if start_idx < end_idx && is_summary(m) => {
+                    start_idx += 1;
+                    m.content
+                        .strip_prefix(SUMMARY_PREFIX)
+                        .map(|s| s.trim().to_string())
+                }
+                _ => None,
+            };
+
             if start_idx >= end_idx {
                 return Ok(());
             }
 
-            let to_summarize = &self.history[start_idx..end_idx];
+            // Pinned messages are carried over verbatim instead of summarized
+            let (pinned, to_summarize): (Vec<&Message>, Vec<&Message>) = self.history
+                [start_idx..end_idx]
+                .iter()
+                .partition(|m| m.metadata.pinned);
+            if to_summarize.is_empty() {
+                return Ok(());
+            }
             let summary_content = to_summarize
                 .iter()
-                .map(|m| format!("{:?}: {}", m.role, m.content))
+                .map(|m| format!("{:?}: {}", m.role, m.rendered_content()))
                 .collect::<Vec<_>>()
                 .join("\n");
 
             // If we have an LLM, use it to summarize. Otherwise just truncate with a placeholder.
-            let summary = if let Some(llm) = &self.llm {
-                let prompt = format!(
-                    "Summarize the following conversation history into a single paragraph. Ignore system messages if any.\n\n{}",
-                    summary_content
+            let summary = if self.summarizer.is_some() || self.llm.is_some() {
+                let instructions = self
+                    .summary_prompt
+                    .clone()
+                    .unwrap_or_else(default_summary_instructions);
+                let prompt = build_summary_prompt(
+                    &instructions,
+                    previous_summary.as_deref(),
+                    &summary_content,
                 );
-                // We use a separate ephemeral request for summary
-                match llm
-                    .complete(&[Message {
-                        role: Role::User,
-                        content: prompt,
-                    }])
-                    .await
-                {
-                    Ok(s) => s,
-                    Err(_) => "... Conversation compressed (summary failed) ...".to_string(),
+                match self.summarize(prompt).await {
+                    Some(s) => s,
+                    None => fallback_summary(
+                        previous_summary.as_deref(),
+                        "... Conversation compressed (summary failed) ...",
+                    ),
                 }
             } else {
-                "... Old conversation compressed ...".to_string()
+                fallback_summary(
+                    previous_summary.as_deref(),
+                    "... Old conversation compressed ...",
+                )
             };
 
-            let summary_msg = Message {
-                role: Role::System,
-                content: format!("Previous conversation summary: {}", summary),
-            };
+            let summary_msg = Message::new(
+                Role::System,
+                format!("{}\n{}", SUMMARY_PREFIX, summary.trim()),
+            );
 
+            let messages_summarized = to_summarize.len();
+            let messages_pinned = pinned.len();
             let mut new_history = Vec::new();
             if let Some(sys) = system_msg {
                 new_history.push(sys);
             }
             new_history.push(summary_msg);
+            new_history.extend(pinned.into_iter().cloned());
             new_history.extend_from_slice(&self.history[end_idx..]);
 
             self.history = new_history;
+            self.compressions.push(CompressionRecord {
+                timestamp: chrono::Utc::now().to_rfc3339(),
+                tokens_before: current_tokens,
+                tokens_after: self.total_tokens(),
+                messages_summarized,
+                messages_pinned,
+            });
         }
 
         Ok(())
     }
+
+    // Run the summary prompt on the summarizer, falling back to the main model
+    async fn summarize(&self, prompt: String) -> Option<String> {
+        // We use a separate ephemeral request for summary
+        let request = [Message::new(Role::User, prompt)];
+        if let Some(summarizer) = &self.summarizer {
+            match summarizer.complete(&request).await {
+                Ok(s) => return Some(s),
+                Err(e) => warn!("Summarizer failed, falling back to main model: {}", e),
+            }
+        }
+        match self.llm.as_ref()?.complete(&request).await {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn!("Context summary failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+fn tool_output_hash(message: &Message) -> Option<u64> {
+    if message.role != Role::Tool
+        || message.metadata.id.is_empty()
+        || message.content.len() < MIN_DEDUP_CHARS
+    {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    message.content.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+const SUMMARY_PREFIX: &str = "Previous conversation summary:";
+
+// Sections every rolling summary keeps, so state survives repeated compression.
+const SUMMARY_SECTIONS: &[&str] = &["Goals", "Decisions", "Files touched", "Open TODOs"];
+
+pub fn is_summary(message: &Message) -> bool {
+    message.role == Role::System && message.content.starts_with(SUMMARY_PREFIX)
+}
+
+fn default_summary_instructions() -> String {
+    let sections = SUMMARY_SECTIONS
+        .iter()
+        .map(|s| format!("## {}", s))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut prompt = String::from(
+        "Summarize the conversation history below into a structured summary using exactly these markdown sections:\n\n",
+    );
+    prompt.push_str(&sections);
+    prompt.push_str("\n\nUse short bullet points under each section and write \"- none\" if a section is empty. Ignore system messages if any.");
+    prompt
+}
+
+fn build_summary_prompt(instructions: &str, previous: Option<&str>, conversation: &str) -> String {
+    let mut prompt = instructions.trim_end().to_string();
+    prompt.push('\n');
+    if let Some(previous) = previous {
+        prompt.push_str("An earlier summary of this conversation is given first. Merge it with the new messages: keep every goal, decision, file and TODO that is still relevant, update ones that changed, and drop TODOs that were completed.\n\n");
+        prompt.push_str("Earlier summary:\n");
+        prompt.push_str(previous);
+        prompt.push_str("\n\nNew messages:\n");
+    } else {
+        prompt.push('\n');
+    }
+    prompt.push_str(conversation);
+    prompt
+}
+
+// Without a usable summary from the LLM, keep the earlier one rather than drop it.
+fn fallback_summary(previous: Option<&str>, placeholder: &str) -> String {
+    match previous {
+        Some(previous) => format!("{}\n{}", previous, placeholder),
+        None => placeholder.to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -136,19 +404,10 @@ mod tests {
     async fn test_context_compression() {
         let mut ctx = ContextManager::new(10); // Very small limit to trigger compression
 
-        ctx.add_message(Message {
-            role: Role::System,
-            content: "You are a bot".to_string(),
-        });
+        ctx.add_message(Message::new(Role::System, "You are a bot".to_string()));
         for i in 0..10 {
-            ctx.add_message(Message {
-                role: Role::User,
-                content: format!("Message {}", i),
-            });
-            ctx.add_message(Message {
-                role: Role::Assistant,
-                content: format!("Reply {}", i),
-            });
+            ctx.add_message(Message::new(Role::User, format!("Message {}", i)));
+            ctx.add_message(Message::new(Role::Assistant, format!("Reply {}", i)));
         }
 
         assert_eq!(ctx.get_history().len(), 21);
@@ -162,5 +421,144 @@ mod tests {
         // 1 System + 1 Summary + 4 last = 6.
         assert_eq!(ctx.get_history().len(), 6);
         assert!(ctx.get_history()[1].content.contains("summary"));
+
+        let record = &ctx.compression_history()[0];
+        assert_eq!(record.messages_summarized, 16);
+        assert!(record.tokens_after < record.tokens_before);
+        assert_eq!(record.tokens_after, ctx.total_tokens());
+    }
+
+    struct RecordingLLM {
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl LLM for RecordingLLM {
+        async fn complete(&self, messages: &[Message]) -> Result<String> {
+            let mut prompts = self.prompts.lock().unwrap();
+            prompts.push(messages[0].content.clone());
+            Ok(format!("## Goals\n- summary {}", prompts.len()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rolling_summary_keeps_previous_summary() {
+        let llm = Arc::new(RecordingLLM {
+            prompts: std::sync::Mutex::new(Vec::new()),
+        });
+        let mut ctx = ContextManager::new(10);
+        ctx.set_llm(llm.clone());
+        ctx.add_message(Message::new(Role::System, "You are a bot".to_string()));
+        for round in 0..2 {
+            for i in 0..10 {
+                ctx.add_message(Message::new(
+                    Role::User,
+                    format!("Round {} message {}", round, i),
+                ));
+            }
+            ctx.compress().await.unwrap();
+        }
+
+        let prompts = llm.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[0].contains("## Open TODOs"));
+        assert!(!prompts[0].contains("Earlier summary"));
+        // The second pass folds in the first summary instead of treating it as a system message.
+        assert!(prompts[1].contains("Earlier summary:\n## Goals\n- summary 1"));
+        assert!(!prompts[1].contains("System: Previous conversation summary"));
+
+        let history = ctx.get_history();
+        assert_eq!(history.len(), 6);
+        assert_eq!(history[0].content, "You are a bot");
+        assert!(history[1].content.starts_with(SUMMARY_PREFIX));
+        assert!(history[1].content.contains("summary 2"));
+    }
+
+    #[tokio::test]
+    async fn test_pinned_messages_survive_compression() {
+        let mut ctx = ContextManager::new(10);
+        ctx.add_message(Message::new(Role::System, "You are a bot"));
+        for i in 0..10 {
+            ctx.add_message(Message::new(Role::User, format!("Message {}", i)));
+        }
+        ctx.pin(2).unwrap();
+        ctx.pin(3).unwrap();
+        ctx.unpin(3).unwrap();
+        assert!(ctx.pin(42).is_err());
+
+        ctx.compress().await.unwrap();
+
+        // System + Summary + 1 pinned + last 4
+        let history = ctx.get_history();
+        assert_eq!(history.len(), 7);
+        assert_eq!(history[2].content, "Message 1");
+        assert!(history[2].metadata.pinned);
+        assert_eq!(ctx.pinned_messages().len(), 1);
+    }
+
+    struct FailingLLM;
+
+    #[async_trait::async_trait]
+    impl LLM for FailingLLM {
+        async fn complete(&self, _messages: &[Message]) -> Result<String> {
+            Err(anyhow::anyhow!("unavailable"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_summarizer_falls_back_to_main_model() {
+        let llm = Arc::new(RecordingLLM {
+            prompts: std::sync::Mutex::new(Vec::new()),
+        });
+        let mut ctx = ContextManager::new(10);
+        ctx.set_llm(llm.clone());
+        ctx.set_summarizer(Arc::new(FailingLLM));
+        ctx.set_summary_prompt("Summarize tersely.".to_string());
+        for i in 0..10 {
+            ctx.add_message(Message::new(Role::User, format!("Message {}", i)));
+        }
+
+        ctx.compress().await.unwrap();
+
+        let prompts = llm.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].starts_with("Summarize tersely.\n"));
+        assert!(!prompts[0].contains("## Open TODOs"));
+        assert!(ctx.get_history()[0].content.contains("summary 1"));
+    }
+
+    #[test]
+    fn test_duplicate_tool_output_is_deduplicated() {
+        let mut ctx = ContextManager::new(1000);
+        let listing = "src/main.rs\n".repeat(50);
+        let call =
+            "<tool_code>\n{\"name\": \"bash\", \"args\": {\"command\": \"ls\"}}\n</tool_code>";
+        ctx.add_message(Message::new(Role::Assistant, call));
+        ctx.add_message(Message::tool("bash", listing.clone()));
+        ctx.add_message(Message::tool("bash", "short"));
+        ctx.add_message(Message::tool("bash", "short"));
+        ctx.add_message(Message::new(Role::Assistant, call));
+        ctx.add_message(Message::tool("bash", listing.clone()));
+
+        // The earlier copy stays; the new one names the call the model can see
+        let history = ctx.get_history();
+        assert_eq!(history[1].content, listing);
+        assert_eq!(history[3].content, "short");
+        assert_eq!(
+            history[5].content,
+            "[Duplicate output omitted: identical to the earlier 'bash' output of the call with args {\"command\":\"ls\"} in this conversation]"
+        );
+
+        // Once the earlier copy is gone, the output is kept again
+        ctx.truncate(1);
+        ctx.add_message(Message::tool("grep", listing.clone()));
+        ctx.add_message(Message::tool("grep", listing.clone()));
+        let history = ctx.get_history();
+        assert_eq!(history[1].content, listing);
+        assert!(
+            history[2].content.starts_with(
+                "[Duplicate output omitted: identical to the earlier 'grep' output in"
+            )
+        );
     }
 }
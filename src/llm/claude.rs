@@ -79,22 +79,7 @@ enum ContentBlockDelta {
 #[async_trait]
 impl LLM for ClaudeClient {
     async fn complete(&self, messages: &[Message]) -> Result<String> {
-        let mut claude_messages = Vec::new();
-        let mut system_prompt = None;
-
-        for m in messages {
-            match m.role {
-                Role::System => system_prompt = Some(m.content.clone()),
-                Role::User => claude_messages.push(ClaudeMessage {
-                    role: "user".to_string(),
-                    content: m.content.clone(),
-                }),
-                Role::Assistant => claude_messages.push(ClaudeMessage {
-                    role: "assistant".to_string(),
-                    content: m.content.clone(),
-                }),
-            }
-        }
+        let (system_prompt, claude_messages) = convert_messages(messages);
 
         let request_body = ClaudeRequest {
             model: self.model.clone(),
@@ -178,3 +163,49 @@ impl LLM for ClaudeClient {
         }
     }
 }
+
+// The API takes a single system prompt, so every System message (the main
+// prompt and any rolling summary) is joined into it in order
+fn convert_messages(messages: &[Message]) -> (Option<String>, Vec<ClaudeMessage>) {
+    let mut claude_messages = Vec::new();
+    let mut system = Vec::new();
+
+    for m in messages {
+        match m.role {
+            Role::System => system.push(m.content.as_str()),
+            Role::User | Role::Tool => claude_messages.push(ClaudeMessage {
+                role: "user".to_string(),
+                content: m.rendered_content(),
+            }),
+            Role::Assistant => claude_messages.push(ClaudeMessage {
+                role: "assistant".to_string(),
+                content: m.content.clone(),
+            }),
+        }
+    }
+
+    let system_prompt = (!system.is_empty()).then(|| system.join("\n\n"));
+    (system_prompt, claude_messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_messages_are_joined() {
+        let messages = [
+            Message::new(Role::System, "Use tools"),
+            Message::new(Role::System, "Previous conversation summary:\n- goal"),
+            Message::new(Role::User, "Go on"),
+        ];
+        let (system, converted) = convert_messages(&messages);
+        assert_eq!(
+            system.as_deref(),
+            Some("Use tools\n\nPrevious conversation summary:\n- goal")
+        );
+        assert_eq!(converted.len(), 1);
+        assert_eq!(converted[0].role, "user");
+        assert_eq!(convert_messages(&messages[2..]).0, None);
+    }
+}
@@ -11,12 +11,86 @@ pub enum Role {
     User,
     Assistant,
     System,
+    Tool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MessageMetadata {
+    // Unique message id
+    pub id: String,
+    // Creation time (RFC 3339)
+    pub timestamp: String,
+    // Tool that produced this message, for Role::Tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_name: Option<String>,
+    // Estimated token count of the content
+    pub token_count: usize,
+    // Pinned messages are never summarized away by compression
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    // The answer of a completed turn, taken from a <final> block
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub final_answer: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
     pub content: String,
+    #[serde(default)]
+    pub metadata: MessageMetadata,
+}
+
+impl Message {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        let content = content.into();
+        let metadata = MessageMetadata {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            tool_name: None,
+            token_count: estimate_tokens(&content),
+            pinned: false,
+            final_answer: false,
+        };
+        Self {
+            role,
+            content,
+            metadata,
+        }
+    }
+
+    pub fn tool(tool_name: &str, output: impl Into<String>) -> Self {
+        let mut message = Self::new(Role::Tool, output);
+        message.metadata.tool_name = Some(tool_name.to_string());
+        message
+    }
+
+    // Answer text extracted from a <final> block, ending the turn
+    pub fn final_answer(text: impl Into<String>) -> Self {
+        let mut message = Self::new(Role::Assistant, text);
+        message.metadata.final_answer = true;
+        message
+    }
+
+    pub fn set_content(&mut self, content: String) {
+        self.metadata.token_count = estimate_tokens(&content);
+        self.content = content;
+    }
+
+    // Content as sent to providers that have no native tool-result message:
+    // tool output goes out as a user turn labelled with the tool name.
+    pub fn rendered_content(&self) -> String {
+        match (&self.role, &self.metadata.tool_name) {
+            (Role::Tool, Some(name)) => format!("Tool '{}' output:\n{}", name, self.content),
+            (Role::Tool, None) => format!("Tool output:\n{}", self.content),
+            _ => self.content.clone(),
+        }
+    }
+}
+
+// Approx 4 chars per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4
 }
 
 #[allow(clippy::upper_case_acronyms)]
@@ -51,3 +125,22 @@ pub fn create_llm(
         _ => Err(anyhow::anyhow!("Unknown provider: {}", provider)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_message() {
+        let msg = Message::tool("bash", "file.txt");
+        assert_eq!(msg.role, Role::Tool);
+        assert_eq!(msg.metadata.tool_name.as_deref(), Some("bash"));
+        assert!(!msg.metadata.id.is_empty());
+        assert_eq!(msg.rendered_content(), "Tool 'bash' output:\nfile.txt");
+
+        // Messages saved before metadata existed still deserialize.
+        let old: Message = serde_json::from_str(r#"{"role":"user","content":"hi"}"#).unwrap();
+        assert!(old.metadata.id.is_empty());
+        assert_eq!(old.rendered_content(), "hi");
+    }
+}
@@ -38,14 +38,16 @@ struct OpenAIMessage {
 
 impl From<&Message> for OpenAIMessage {
     fn from(m: &Message) -> Self {
+        // The "tool" role requires native tool_call ids, which the text-based
+        // tool protocol does not produce, so tool results go out as user turns.
         let role = match m.role {
-            Role::User => "user",
+            Role::User | Role::Tool => "user",
             Role::Assistant => "assistant",
             Role::System => "system",
         };
         Self {
             role: role.to_string(),
-            content: m.content.clone(),
+            content: m.rendered_content(),
         }
     }
 }
@@ -125,10 +127,7 @@ mod tests {
         let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY must be set");
         let client = OpenAIClient::new(api_key, "gpt-3.5-turbo".to_string(), None);
 
-        let messages = vec![Message {
-            role: Role::User,
-            content: "Hello, say 'test passed'".to_string(),
-        }];
+        let messages = vec![Message::new(Role::User, "Hello, say 'test passed'")];
 
         let result = client.complete(&messages).await;
         assert!(result.is_ok());
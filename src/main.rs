@@ -2,19 +2,34 @@ mod cli;
 mod context;
 mod llm;
 mod mcp;
+mod memory;
+mod project;
+mod prompt;
 mod session;
+mod spill;
 mod subagent;
 mod tool;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use cli::Cli;
 use context::ContextManager;
 use dotenv::dotenv;
 use llm::create_llm;
+use memory::MemoryStore;
+use project::ProjectContext;
+use prompt::PromptTemplates;
 use session::SessionManager;
-use std::path::PathBuf;
+use session::crypto::SessionCipher;
+use session::dataset::{DatasetFilter, DatasetFormat, Redactor, RegexRedactor};
+use session::import::ImportFormat;
+use session::retention::RetentionPolicy;
+use spill::SpillStore;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
-use tool::{BashTool, SubAgentTool, ToolRegistry};
+use tool::{
+    BashTool, EditFileTool, GlobTool, GrepTool, MemoryTool, ReadFileTool, SubAgentTool,
+    ToolOutputTool, ToolRegistry, WriteFileTool,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -39,6 +54,38 @@ struct Args {
     #[arg(long, default_value = "__sessions")]
     session_dir: String,
 
+    // Session storage backend (dir, sqlite)
+    #[arg(long, default_value = "dir")]
+    session_store: String,
+
+    // File with the session encryption key (defaults to $MINI_AGENT_SESSION_KEY)
+    #[arg(long)]
+    session_key_file: Option<String>,
+
+    // Previous versions to keep when a saved session is overwritten (dir store only)
+    #[arg(long, default_value_t = 0)]
+    session_backups: usize,
+
+    // Delete sessions not active for this many days at startup
+    #[arg(long)]
+    retention_max_age_days: Option<u64>,
+
+    // Keep at most this many sessions, deleting the oldest at startup
+    #[arg(long)]
+    retention_max_count: Option<usize>,
+
+    // Keep the session directory under this many MB, deleting the oldest at startup
+    #[arg(long)]
+    retention_max_size_mb: Option<u64>,
+
+    // Resume the most recent session
+    #[arg(long = "continue", default_value_t = false, conflicts_with = "resume")]
+    continue_session: bool,
+
+    // Resume a session by id; without an id, pick one interactively
+    #[arg(long, num_args = 0..=1, default_missing_value = "")]
+    resume: Option<String>,
+
     // Maximum agent loops
     #[arg(long, default_value_t = 50)]
     max_loops: usize,
@@ -62,6 +109,140 @@ struct Args {
     // Context max tokens
     #[arg(long, default_value_t = 8192)]
     max_tokens: usize,
+
+    // Summarizer LLM provider for context compression (defaults to --provider)
+    #[arg(long)]
+    summary_provider: Option<String>,
+
+    // Summarizer model name; compression uses the main model if unset
+    #[arg(long)]
+    summary_model: Option<String>,
+
+    // Summarizer API Key (optional, defaults to the provider env var)
+    #[arg(long)]
+    summary_api_key: Option<String>,
+
+    // Summarizer API URL (optional, defaults to --api-url for the same provider)
+    #[arg(long)]
+    summary_api_url: Option<String>,
+
+    // File with custom summary instructions for context compression
+    #[arg(long)]
+    summary_prompt_file: Option<String>,
+
+    // Directory with prompt template overrides (system.md, subagent_code.md, ...)
+    #[arg(long)]
+    prompt_dir: Option<String>,
+
+    // Don't add working directory, git and project instructions to prompts
+    #[arg(long, default_value_t = false)]
+    disable_project_context: bool,
+
+    // Don't autosave the session transcript
+    #[arg(long, default_value_t = false)]
+    no_autosave: bool,
+
+    // Disable long-term memory
+    #[arg(long, default_value_t = false)]
+    disable_memory: bool,
+
+    // Tool outputs longer than this many chars are elided in the context
+    #[arg(long, default_value_t = spill::DEFAULT_MAX_OUTPUT_CHARS)]
+    max_tool_output: usize,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    // Manage saved sessions without starting the interactive CLI
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SessionsCommand {
+    // Full-text search across saved sessions and transcripts
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        // Maximum number of matching turns to show
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    // List saved sessions and transcripts, most recent first
+    List,
+    // Import an OpenAI, Anthropic or ShareGPT conversation as a new session
+    Import {
+        path: String,
+        id: String,
+
+        // openai, anthropic or sharegpt; detected from the file if unset
+        #[arg(long)]
+        format: Option<String>,
+    },
+    // Delete sessions with their transcripts and spilled tool outputs
+    Delete {
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    // Rename a session
+    Rename {
+        from: String,
+        to: String,
+    },
+    // Add tags to a saved session (or remove them with --remove) and show its tags
+    Tag {
+        id: String,
+        tags: Vec<String>,
+
+        #[arg(long)]
+        remove: Vec<String>,
+    },
+    // Write saved sessions as a JSONL fine-tuning dataset
+    Dataset {
+        output: String,
+
+        // openai or anthropic
+        #[arg(long, default_value = "openai")]
+        format: String,
+
+        // Only sessions whose last answer is a <final> answer
+        #[arg(long, default_value_t = false)]
+        require_final: bool,
+
+        // Only sessions with any of these tags
+        #[arg(long)]
+        tag: Vec<String>,
+
+        // Extra redaction rules: one regex per line, optionally followed by a
+        // tab and the replacement
+        #[arg(long)]
+        redact_rules: Option<String>,
+
+        // Skip the built-in redaction of e-mail addresses, keys and tokens
+        #[arg(long, default_value_t = false)]
+        no_redact: bool,
+    },
+    // Delete old sessions; limits default to the --retention-* options
+    Prune {
+        #[arg(long)]
+        max_age_days: Option<u64>,
+
+        #[arg(long)]
+        max_count: Option<usize>,
+
+        #[arg(long)]
+        max_size_mb: Option<u64>,
+
+        // Only show what would be deleted
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+    },
 }
 
 use env_logger::Builder;
@@ -112,29 +293,116 @@ async fn main() -> anyhow::Result<()> {
     builder.target(env_logger::Target::Pipe(Box::new(log_file)));
     builder.init();
 
-    let api_key = args
-        .api_key
-        .as_ref()
-        .cloned()
-        .or_else(|| match args.provider.to_lowercase().as_str() {
-            "openai" => std::env::var("OPENAI_API_KEY").ok(),
-            "claude" => std::env::var("ANTHROPIC_API_KEY").ok(),
-            "minimax" => std::env::var("MINIMAX_API_KEY").ok(),
-            _ => None,
-        })
+    if let Some(Command::Sessions { action }) = &args.command {
+        return run_sessions_command(&args, action);
+    }
+
+    let api_key = resolve_api_key(&args.provider, args.api_key.as_ref())
         .expect("API Key must be provided via --api-key or env var (e.g. ANTHROPIC_API_KEY)");
 
     // Initialize components
-    let llm = create_llm(&args.provider, &args.model, &api_key, args.api_url)?;
+    let llm = create_llm(&args.provider, &args.model, &api_key, args.api_url.clone())?;
     let llm: Arc<dyn llm::LLM> = Arc::from(llm);
 
     let mut context = ContextManager::new(args.max_tokens); // Max tokens
     context.set_llm(llm.clone()); // Enable compression with LLM
-    let session_manager = SessionManager::new(PathBuf::from(&args.session_dir));
+    if let Some(summary_model) = &args.summary_model {
+        let provider = args.summary_provider.as_ref().unwrap_or(&args.provider);
+        let api_key = resolve_api_key(provider, args.summary_api_key.as_ref())
+            .or_else(|| Some(api_key.clone()).filter(|_| provider == &args.provider))
+            .expect("Summarizer API Key must be provided via --summary-api-key or env var");
+        let api_url = if args.summary_api_url.is_some() {
+            args.summary_api_url.clone()
+        } else if provider == &args.provider {
+            args.api_url.clone()
+        } else {
+            None
+        };
+        let summarizer = create_llm(provider, summary_model, &api_key, api_url)?;
+        context.set_summarizer(Arc::from(summarizer));
+    }
+    if let Some(path) = &args.summary_prompt_file {
+        context.set_summary_prompt(std::fs::read_to_string(path)?);
+    }
+    let mut session_manager = open_session_manager(&args)?;
+    session_manager.set_model_info(&args.provider, &args.model);
+    let retention = retention_policy(
+        args.retention_max_age_days,
+        args.retention_max_count,
+        args.retention_max_size_mb,
+    );
+    let resume_id = if args.continue_session {
+        match session_manager.list_recent_sessions()?.first() {
+            Some(entry) => Some(entry.id.clone()),
+            None => {
+                println!("No previous session to continue");
+                None
+            }
+        }
+    } else {
+        match args.resume.as_deref() {
+            Some("") => cli::pick_session(&session_manager.list_recent_sessions()?)?,
+            Some(id) => Some(id.to_string()),
+            None => None,
+        }
+    };
+    // Pruned after picking the session to resume, so that one is kept
+    if !retention.is_empty() {
+        match session_manager.prune(&retention, false, resume_id.as_deref()) {
+            Ok(pruned) if !pruned.is_empty() => {
+                log::info!("Pruned {} sessions: {}", pruned.len(), pruned.join(", "))
+            }
+            Ok(_) => {}
+            Err(e) => log::error!("Session pruning failed: {}", e),
+        }
+    }
+    if let Some(id) = &resume_id {
+        session_manager
+            .load_session(id, &mut context)
+            .map_err(|e| anyhow::anyhow!("Failed to resume session '{}': {}", id, e))?;
+        println!("Resumed session: {}", id);
+    }
+    // A resumed session keeps autosaving into its own transcript
+    if !args.no_autosave
+        && let Err(e) = session_manager.start_autosave(resume_id.as_deref())
+    {
+        log::error!("Session autosave disabled: {}", e);
+    }
 
     let mut tool_registry = ToolRegistry::new();
     tool_registry.register(Arc::new(BashTool));
+    tool_registry.register(Arc::new(ReadFileTool));
+    tool_registry.register(Arc::new(WriteFileTool));
+    tool_registry.register(Arc::new(EditFileTool));
+    tool_registry.register(Arc::new(GlobTool));
+    tool_registry.register(Arc::new(GrepTool));
     tool_registry.register(Arc::new(SubAgentTool));
+    // Oversized tool outputs are kept per session under the session directory
+    let spill_id = session_manager
+        .current_session_id()
+        .map(String::from)
+        .or_else(|| resume_id.clone())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let spill_store = Arc::new(
+        SpillStore::new(session_manager.spill_dir(&spill_id), args.max_tool_output)?
+            .with_cipher(session_manager.cipher().cloned()),
+    );
+    tool_registry.register(Arc::new(ToolOutputTool::new(spill_store.clone())));
+    tool_registry.set_spill_store(spill_store);
+    let cwd = std::env::current_dir()?;
+    tool_registry.set_prompt_templates(PromptTemplates::with_default_dirs(
+        args.prompt_dir.as_ref().map(PathBuf::from),
+        &project::project_root(&cwd),
+    ));
+    if !args.disable_project_context {
+        tool_registry.set_project_context(ProjectContext::discover(&cwd));
+    }
+    if !args.disable_memory {
+        // Started from a subdirectory, the agent still shares the project's memory
+        let memory_store = Arc::new(MemoryStore::open_default(&project::project_root(&cwd)));
+        tool_registry.register(Arc::new(MemoryTool::new(memory_store.clone())));
+        tool_registry.set_memory_store(memory_store);
+    }
     // Register other tools or MCP tools here
     if !args.disable_mcp
         && let Err(e) = mcp::register_mcp_tools(&mut tool_registry, &args.mcp_config).await
@@ -143,7 +411,142 @@ async fn main() -> anyhow::Result<()> {
     }
 
     let mut ui = Cli::new(context, session_manager, tool_registry, llm, args.max_loops);
+    ui.set_current_session(resume_id);
 
     ui.run().await?;
     Ok(())
 }
+
+fn open_session_manager(args: &Args) -> anyhow::Result<SessionManager> {
+    let dir = PathBuf::from(&args.session_dir);
+    let cipher = SessionCipher::load(args.session_key_file.as_ref().map(Path::new))?;
+    let store = session::storage::open_store(
+        &args.session_store,
+        &dir,
+        args.session_backups,
+        cipher.clone(),
+    )?;
+    let mut session_manager = SessionManager::new(dir, store);
+    session_manager.set_cipher(cipher);
+    Ok(session_manager)
+}
+
+fn run_sessions_command(args: &Args, action: &SessionsCommand) -> anyhow::Result<()> {
+    let mut session_manager = open_session_manager(args)?;
+    match action {
+        SessionsCommand::Search { query, limit } => {
+            let hits = session_manager.search(&query.join(" "), *limit)?;
+            if hits.is_empty() {
+                println!("No matches");
+            } else {
+                cli::print_search_hits(&hits);
+                println!("Resume one with: mini-agent --resume <id>");
+            }
+        }
+        SessionsCommand::List => cli::print_session_table(&session_manager.list_recent_sessions()?),
+        SessionsCommand::Import { path, id, format } => {
+            let format = format.as_deref().map(ImportFormat::parse).transpose()?;
+            let ids = session_manager.import_session(Path::new(path), id, format)?;
+            println!("Imported as {}", ids.join(", "));
+        }
+        SessionsCommand::Delete { ids } => {
+            for id in ids {
+                session_manager.delete_session(id)?;
+                println!("Deleted {}", id);
+            }
+        }
+        SessionsCommand::Rename { from, to } => {
+            session_manager.rename_session(from, to)?;
+            println!("Renamed {} to {}", from, to);
+        }
+        SessionsCommand::Tag { id, tags, remove } => {
+            let tags = session_manager.update_session_tags(id, tags, remove)?;
+            if tags.is_empty() {
+                println!("{} has no tags", id);
+            } else {
+                println!("{}: {}", id, tags.join(", "));
+            }
+        }
+        SessionsCommand::Dataset {
+            output,
+            format,
+            require_final,
+            tag,
+            redact_rules,
+            no_redact,
+        } => {
+            let mut redactors: Vec<Box<dyn Redactor>> = Vec::new();
+            if !no_redact {
+                redactors.push(Box::new(RegexRedactor::with_default_rules()));
+            }
+            if let Some(path) = redact_rules {
+                redactors.push(Box::new(RegexRedactor::from_rules_file(Path::new(path))?));
+            }
+            let filter = DatasetFilter {
+                require_final: *require_final,
+                tags: tag.clone(),
+            };
+            let (count, skipped) = session_manager.export_dataset(
+                DatasetFormat::parse(format)?,
+                &filter,
+                &redactors,
+                Path::new(output),
+            )?;
+            println!("Wrote {} session(s) to {}", count, output);
+            if skipped > 0 {
+                println!("Skipped {} unreadable session(s)", skipped);
+            }
+        }
+        SessionsCommand::Prune {
+            max_age_days,
+            max_count,
+            max_size_mb,
+            dry_run,
+        } => {
+            let policy = retention_policy(
+                max_age_days.or(args.retention_max_age_days),
+                max_count.or(args.retention_max_count),
+                max_size_mb.or(args.retention_max_size_mb),
+            );
+            if policy.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "No retention limit given (--max-age-days, --max-count or --max-size-mb)"
+                ));
+            }
+            let pruned = session_manager.prune(&policy, *dry_run, None)?;
+            let verb = if *dry_run { "Would delete" } else { "Deleted" };
+            for id in &pruned {
+                println!("{} {}", verb, id);
+            }
+            println!(
+                "{} session(s) {}",
+                pruned.len(),
+                if *dry_run { "to prune" } else { "pruned" }
+            );
+        }
+    }
+    Ok(())
+}
+
+fn retention_policy(
+    max_age_days: Option<u64>,
+    max_count: Option<usize>,
+    max_size_mb: Option<u64>,
+) -> RetentionPolicy {
+    RetentionPolicy {
+        max_age_days,
+        max_count,
+        max_size_bytes: max_size_mb.map(|mb| mb.saturating_mul(1024 * 1024)),
+    }
+}
+
+fn resolve_api_key(provider: &str, explicit: Option<&String>) -> Option<String> {
+    explicit
+        .cloned()
+        .or_else(|| match provider.to_lowercase().as_str() {
+            "openai" => std::env::var("OPENAI_API_KEY").ok(),
+            "claude" => std::env::var("ANTHROPIC_API_KEY").ok(),
+            "minimax" => std::env::var("MINIMAX_API_KEY").ok(),
+            _ => None,
+        })
+}
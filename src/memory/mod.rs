@@ -0,0 +1,336 @@
+use crate::session::write_atomic;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+// Upper bound on memory text injected into the system prompt
+const MAX_PROMPT_CHARS: usize = 4000;
+// Shorter words and these common ones say nothing about relevance
+const MIN_KEYWORD_CHARS: usize = 3;
+const STOP_WORDS: &[&str] = &[
+    "the", "and", "for", "with", "that", "this", "are", "was", "use", "from", "not", "but", "you",
+    "all", "can", "has", "have", "into", "when", "what", "how", "why", "should", "please", "would",
+    "could",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryScope {
+    // Facts about the current project, stored in the project directory
+    Project,
+    // Facts about the user, shared by all projects
+    User,
+}
+
+impl MemoryScope {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "project" => Ok(Self::Project),
+            "user" => Ok(Self::User),
+            _ => Err(anyhow::anyhow!(
+                "Unknown memory scope '{}' (expected project or user)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryEntry {
+    pub id: String,
+    pub scope: MemoryScope,
+    pub content: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// Long-term facts that survive between sessions, persisted as one JSON file per scope
+pub struct MemoryStore {
+    project_path: PathBuf,
+    user_path: Option<PathBuf>,
+    entries: Mutex<Vec<MemoryEntry>>,
+}
+
+impl MemoryStore {
+    pub fn new(project_path: PathBuf, user_path: Option<PathBuf>) -> Self {
+        let mut entries = load_entries(&project_path, MemoryScope::Project);
+        if let Some(path) = &user_path {
+            entries.extend(load_entries(path, MemoryScope::User));
+        }
+        Self {
+            project_path,
+            user_path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    // Project store under `.mini-agent/` in the project root, user store under $HOME
+    pub fn open_default(project_root: &Path) -> Self {
+        let user_path = std::env::var("HOME")
+            .ok()
+            .map(|home| PathBuf::from(home).join(".mini-agent").join("memory.json"));
+        Self::new(
+            project_root.join(".mini-agent").join("memory.json"),
+            user_path,
+        )
+    }
+
+    pub fn list(&self) -> Vec<MemoryEntry> {
+        self.entries
+            .lock()
+            .expect("MemoryStore lock poisoned")
+            .clone()
+    }
+
+    // Changes are made on a copy that replaces the entries only once it is
+    // on disk, so a failed write leaves the store as it was
+    pub fn save(&self, scope: MemoryScope, content: &str) -> Result<MemoryEntry> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let entry = MemoryEntry {
+            id: Uuid::new_v4()
+                .to_string()
+                .split('-')
+                .next()
+                .unwrap_or("")
+                .to_string(),
+            scope,
+            content: content.trim().to_string(),
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        let mut entries = self.entries.lock().expect("MemoryStore lock poisoned");
+        let mut updated = entries.clone();
+        updated.push(entry.clone());
+        self.persist(&updated, scope)?;
+        *entries = updated;
+        Ok(entry)
+    }
+
+    pub fn update(&self, id: &str, content: &str) -> Result<MemoryEntry> {
+        let mut entries = self.entries.lock().expect("MemoryStore lock poisoned");
+        let mut updated = entries.clone();
+        let entry = updated
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Memory '{}' not found", id))?;
+        entry.content = content.trim().to_string();
+        entry.updated_at = chrono::Utc::now().to_rfc3339();
+        let entry = entry.clone();
+        self.persist(&updated, entry.scope)?;
+        *entries = updated;
+        Ok(entry)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<MemoryEntry> {
+        let mut entries = self.entries.lock().expect("MemoryStore lock poisoned");
+        let mut updated = entries.clone();
+        let pos = updated
+            .iter()
+            .position(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("Memory '{}' not found", id))?;
+        let removed = updated.remove(pos);
+        self.persist(&updated, removed.scope)?;
+        *entries = updated;
+        Ok(removed)
+    }
+
+    // Memory section for the system prompt: only entries sharing a keyword
+    // with `query` (the current task and project), best matches first. The
+    // rest are only counted, so the model knows to list them when needed.
+    pub fn prompt_section(&self, query: &str) -> String {
+        let entries = self.list();
+        if entries.is_empty() {
+            return String::new();
+        }
+        let query = keywords(query);
+        let mut scored: Vec<(usize, MemoryEntry)> = entries
+            .into_iter()
+            .map(|e| (keywords(&e.content).intersection(&query).count(), e))
+            .collect();
+        let total = scored.len();
+        scored.retain(|(score, _)| *score > 0);
+        scored.sort_by(|(a, x), (b, y)| b.cmp(a).then_with(|| y.updated_at.cmp(&x.updated_at)));
+        let relevant: Vec<MemoryEntry> = scored.into_iter().map(|(_, e)| e).collect();
+        render_entries(
+            "Long-term memory relevant to the current task (facts saved in earlier sessions; keep them up to date with the memory tool):\n",
+            &relevant,
+            total - relevant.len(),
+        )
+    }
+
+    // Every entry, most recently updated first, as shown by the memory tool
+    pub fn list_section(&self) -> String {
+        let mut entries = self.list();
+        if entries.is_empty() {
+            return String::new();
+        }
+        entries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        render_entries("Long-term memory:\n", &entries, 0)
+    }
+
+    fn persist(&self, entries: &[MemoryEntry], scope: MemoryScope) -> Result<()> {
+        let path = match scope {
+            MemoryScope::Project => &self.project_path,
+            MemoryScope::User => self
+                .user_path
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("No user memory location (HOME is not set)"))?,
+        };
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        let scoped: Vec<&MemoryEntry> = entries.iter().filter(|e| e.scope == scope).collect();
+        write_atomic(path, &serde_json::to_vec_pretty(&scoped)?)
+            .with_context(|| format!("Failed to write memory file {}", path.display()))
+    }
+}
+
+fn render_entries(header: &str, entries: &[MemoryEntry], not_shown: usize) -> String {
+    let mut section = String::from(header);
+    let mut used = 0;
+    let mut omitted = not_shown;
+    for (i, entry) in entries.iter().enumerate() {
+        let line = format!(
+            "- [{}] ({}) {}\n",
+            entry.id,
+            match entry.scope {
+                MemoryScope::Project => "project",
+                MemoryScope::User => "user",
+            },
+            entry.content
+        );
+        used += line.len();
+        if used > MAX_PROMPT_CHARS {
+            omitted += entries.len() - i;
+            break;
+        }
+        section.push_str(&line);
+    }
+    if omitted > 0 {
+        section.push_str(&format!(
+            "- ... {} more entries omitted; use the memory tool's list action to see them\n",
+            omitted
+        ));
+    }
+    section
+}
+
+// Lowercase words of at least three characters, for matching entries to a task
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_KEYWORD_CHARS)
+        .map(|w| w.to_lowercase())
+        .filter(|w| !STOP_WORDS.contains(&w.as_str()))
+        .collect()
+}
+
+fn load_entries(path: &PathBuf, scope: MemoryScope) -> Vec<MemoryEntry> {
+    let Ok(content) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    match serde_json::from_str::<Vec<MemoryEntry>>(&content) {
+        Ok(mut entries) => {
+            for e in &mut entries {
+                e.scope = scope;
+            }
+            entries
+        }
+        Err(e) => {
+            log::warn!("Ignoring invalid memory file {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_memory_store() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project.json");
+        let user = dir.path().join("user.json");
+        let store = MemoryStore::new(project.clone(), Some(user.clone()));
+
+        let a = store
+            .save(MemoryScope::Project, "Use cargo nextest")
+            .unwrap();
+        let b = store.save(MemoryScope::User, "Prefers tabs").unwrap();
+        store.update(&b.id, "Prefers spaces").unwrap();
+        let c = store.save(MemoryScope::Project, "temporary").unwrap();
+        store.delete(&c.id).unwrap();
+        assert!(store.delete(&c.id).is_err());
+
+        let reopened = MemoryStore::new(project, Some(user));
+        let entries = reopened.list();
+        assert_eq!(entries.len(), 2);
+        let listed = reopened.list_section();
+        assert!(listed.contains(&format!("[{}] (project) Use cargo nextest", a.id)));
+        assert!(listed.contains("(user) Prefers spaces"));
+        assert!(!listed.contains("temporary"));
+    }
+
+    #[test]
+    fn test_failed_write_keeps_entries() {
+        let dir = tempdir().unwrap();
+        let user_dir = dir.path().join("user");
+        let store = MemoryStore::new(
+            dir.path().join("project.json"),
+            Some(user_dir.join("memory.json")),
+        );
+        let entry = store.save(MemoryScope::User, "Prefers tabs").unwrap();
+
+        // The user store can no longer be written: its directory is now a file
+        fs::remove_dir_all(&user_dir).unwrap();
+        fs::write(&user_dir, "").unwrap();
+        assert!(store.save(MemoryScope::User, "Prefers dark mode").is_err());
+        assert!(store.update(&entry.id, "Prefers spaces").is_err());
+        assert!(store.delete(&entry.id).is_err());
+        let entries = store.list();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].content, "Prefers tabs");
+    }
+
+    #[test]
+    fn test_prompt_section_keeps_relevant_entries() {
+        let dir = tempdir().unwrap();
+        let store = MemoryStore::new(dir.path().join("project.json"), None);
+        store
+            .save(MemoryScope::Project, "Run tests with cargo nextest")
+            .unwrap();
+        store
+            .save(
+                MemoryScope::Project,
+                "Deploys go through the staging cluster",
+            )
+            .unwrap();
+        store
+            .save(
+                MemoryScope::Project,
+                "Cargo features: sqlite, tests need --all-features",
+            )
+            .unwrap();
+
+        let prompt = store.prompt_section("Why do the cargo tests fail with sqlite?");
+        let lines: Vec<&str> = prompt.lines().skip(1).collect();
+        assert_eq!(lines.len(), 3);
+        // Entries matching more keywords come first
+        assert!(lines[0].ends_with("Cargo features: sqlite, tests need --all-features"));
+        assert!(lines[1].ends_with("Run tests with cargo nextest"));
+        assert!(lines[2].contains("1 more entries omitted"));
+        assert!(!prompt.contains("staging"));
+
+        // Nothing matches before there is a task
+        let prompt = store.prompt_section("");
+        assert_eq!(prompt.lines().count(), 2);
+        assert!(prompt.contains("3 more entries omitted"));
+    }
+}
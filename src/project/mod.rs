@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Instruction files looked up in each directory from cwd up to the project
+// root: the git work tree root, else the home directory, else the filesystem root
+const INSTRUCTION_FILES: &[&str] = &["AGENTS.md", ".mini-agent/instructions.md"];
+// Per-file cap on instruction text included in the prompt
+const MAX_INSTRUCTION_CHARS: usize = 8000;
+// Changed files listed in the git status summary
+const MAX_STATUS_LINES: usize = 10;
+
+// Facts about where the agent is running, rendered into system prompts
+#[derive(Debug, Clone, Default)]
+pub struct ProjectContext {
+    pub cwd: PathBuf,
+    pub os: String,
+    pub date: String,
+    pub git_branch: Option<String>,
+    pub git_status: Option<String>,
+    // (path, content) of instruction files, nearest directory first
+    pub instructions: Vec<(PathBuf, String)>,
+}
+
+impl ProjectContext {
+    pub fn discover(cwd: &Path) -> Self {
+        let git_branch = git(cwd, &["rev-parse", "--abbrev-ref", "HEAD"]);
+        let git_status = git_branch
+            .as_ref()
+            .and_then(|_| git(cwd, &["status", "--porcelain"]))
+            .map(|status| summarize_status(&status));
+        let root = git(cwd, &["rev-parse", "--show-toplevel"])
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var("HOME")
+                    .ok()
+                    .map(PathBuf::from)
+                    .filter(|home| cwd.starts_with(home))
+            });
+        Self {
+            cwd: cwd.to_path_buf(),
+            os: format!("{} ({})", std::env::consts::OS, std::env::consts::ARCH),
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            git_branch,
+            git_status,
+            instructions: find_instruction_files(cwd, root.as_deref()),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::from("Environment:\n");
+        out.push_str(&format!("- Working directory: {}\n", self.cwd.display()));
+        out.push_str(&format!("- OS: {}\n", self.os));
+        out.push_str(&format!("- Date: {}\n", self.date));
+        if let Some(branch) = &self.git_branch {
+            out.push_str(&format!("- Git branch: {}\n", branch));
+        }
+        if let Some(status) = &self.git_status {
+            out.push_str(&format!("- Git status: {}\n", status));
+        }
+        for (path, content) in &self.instructions {
+            out.push_str(&format!(
+                "\nProject instructions from {}:\n{}\n",
+                path.display(),
+                content.trim()
+            ));
+        }
+        out
+    }
+}
+
+// Root of the project cwd belongs to: the git work tree root, else cwd itself
+pub fn project_root(cwd: &Path) -> PathBuf {
+    git(cwd, &["rev-parse", "--show-toplevel"])
+        .map(PathBuf::from)
+        .unwrap_or_else(|| cwd.to_path_buf())
+}
+
+fn git(cwd: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(cwd)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn summarize_status(porcelain: &str) -> String {
+    let lines: Vec<&str> = porcelain.lines().collect();
+    if lines.is_empty() {
+        return "clean".to_string();
+    }
+    let mut summary = format!("{} changed file(s)", lines.len());
+    for line in lines.iter().take(MAX_STATUS_LINES) {
+        summary.push_str(&format!("\n    {}", line.trim()));
+    }
+    if lines.len() > MAX_STATUS_LINES {
+        summary.push_str("\n    ...");
+    }
+    summary
+}
+
+// Nearest directory first, stopping after `root` when it is an ancestor of cwd
+fn find_instruction_files(cwd: &Path, root: Option<&Path>) -> Vec<(PathBuf, String)> {
+    let mut found = Vec::new();
+    for dir in cwd.ancestors() {
+        for name in INSTRUCTION_FILES {
+            let path = dir.join(name);
+            if let Ok(content) = fs::read_to_string(&path) {
+                let content = match content.char_indices().nth(MAX_INSTRUCTION_CHARS) {
+                    Some((i, _)) => format!("{}\n... (truncated)", &content[..i]),
+                    None => content,
+                };
+                found.push((path, content));
+            }
+        }
+        if Some(dir) == root {
+            break;
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_discover_instruction_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("project");
+        let nested = root.join("crates").join("app");
+        fs::create_dir_all(nested.join(".mini-agent")).unwrap();
+        fs::write(dir.path().join("AGENTS.md"), "Outside rules").unwrap();
+        fs::write(root.join("AGENTS.md"), "Root rules").unwrap();
+        fs::write(nested.join(".mini-agent/instructions.md"), "App rules").unwrap();
+
+        // Files above the project root are not picked up
+        let instructions = find_instruction_files(&nested, Some(&root));
+        let contents: Vec<&str> = instructions.iter().map(|(_, c)| c.as_str()).collect();
+        assert_eq!(contents, ["App rules", "Root rules"]);
+
+        let ctx = ProjectContext {
+            cwd: nested.clone(),
+            instructions,
+            ..ProjectContext::default()
+        };
+
+        let rendered = ctx.render();
+        assert!(rendered.contains(&format!("- Working directory: {}", nested.display())));
+        assert!(rendered.find("App rules").unwrap() < rendered.find("Root rules").unwrap());
+    }
+
+    #[test]
+    fn test_project_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let nested = root.join("src").join("bin");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(project_root(&nested), nested);
+
+        let init = Command::new("git").arg("init").current_dir(&root).output();
+        if !init.is_ok_and(|o| o.status.success()) {
+            return;
+        }
+        assert_eq!(project_root(&nested), root);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Built-in templates; a `<name>.md` file in an override directory replaces one
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("system", include_str!("templates/system.md")),
+    ("subagent", include_str!("templates/subagent.md")),
+    ("subagent_code", include_str!("templates/subagent_code.md")),
+    ("subagent_test", include_str!("templates/subagent_test.md")),
+    ("subagent_doc", include_str!("templates/subagent_doc.md")),
+    (
+        "subagent_analysis",
+        include_str!("templates/subagent_analysis.md"),
+    ),
+    (
+        "subagent_dynamic",
+        include_str!("templates/subagent_dynamic.md"),
+    ),
+];
+
+// Prompt templates with `{{variable}}` placeholders. Override files are read
+// at render time, so edits take effect on the next prompt without a restart.
+#[derive(Debug, Clone, Default)]
+pub struct PromptTemplates {
+    // Override directories, highest priority first
+    override_dirs: Vec<PathBuf>,
+}
+
+impl PromptTemplates {
+    pub fn new(override_dirs: Vec<PathBuf>) -> Self {
+        Self { override_dirs }
+    }
+
+    // Optional explicit directory, then `.mini-agent/prompts` in the project
+    // root, then `~/.mini-agent/prompts`
+    pub fn with_default_dirs(prompt_dir: Option<PathBuf>, project_root: &Path) -> Self {
+        let mut dirs: Vec<PathBuf> = prompt_dir.into_iter().collect();
+        dirs.push(project_root.join(".mini-agent").join("prompts"));
+        if let Ok(home) = std::env::var("HOME") {
+            dirs.push(PathBuf::from(home).join(".mini-agent").join("prompts"));
+        }
+        Self::new(dirs)
+    }
+
+    pub fn get(&self, name: &str) -> String {
+        for dir in &self.override_dirs {
+            let path = dir.join(format!("{}.md", name));
+            if let Ok(content) = fs::read_to_string(&path) {
+                log::debug!("Using prompt template override {}", path.display());
+                return content.trim_end_matches('\n').to_string();
+            }
+        }
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, t)| t.trim_end_matches('\n').to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn render(&self, name: &str, vars: &HashMap<&str, String>) -> String {
+        render_template(&self.get(name), vars)
+    }
+}
+
+// Replace `{{name}}` placeholders; unknown placeholders are left as they are
+pub fn render_template(template: &str, vars: &HashMap<&str, String>) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let key = after[..end].trim();
+                match vars.get(key) {
+                    Some(value) => out.push_str(value),
+                    None => out.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_render_with_override() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("subagent_code.md"),
+            "Custom {{agent_type}} agent for: {{ task }} {{unknown}}\n",
+        )
+        .unwrap();
+        let templates = PromptTemplates::new(vec![dir.path().to_path_buf()]);
+
+        let vars = HashMap::from([
+            ("agent_type", "code".to_string()),
+            ("task", "fix bug".to_string()),
+        ]);
+        assert_eq!(
+            templates.render("subagent_code", &vars),
+            "Custom code agent for: fix bug {{unknown}}"
+        );
+        // Templates without an override fall back to the built-in text
+        assert!(
+            templates
+                .get("subagent_test")
+                .starts_with("You are a Test SubAgent")
+        );
+    }
+
+    #[test]
+    fn test_override_dir_resolves_from_project_root() {
+        let root = tempdir().unwrap();
+        let prompts = root.path().join(".mini-agent").join("prompts");
+        fs::create_dir_all(&prompts).unwrap();
+        fs::write(prompts.join("system.md"), "Project system prompt\n").unwrap();
+
+        let templates = PromptTemplates::with_default_dirs(None, root.path());
+        assert_eq!(templates.get("system"), "Project system prompt");
+    }
+}
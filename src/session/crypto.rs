@@ -0,0 +1,147 @@
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::path::Path;
+
+// Environment variable holding the session encryption key
+pub const KEY_ENV_VAR: &str = "MINI_AGENT_SESSION_KEY";
+// Encrypted files (and transcript lines) start with this marker, followed by
+// base64 of the 12-byte nonce and the ChaCha20-Poly1305 ciphertext
+const MARKER: &str = "mini-agent-encrypted:v1:";
+const NONCE_LEN: usize = 12;
+
+// Authenticated encryption for session files. Reading accepts both encrypted
+// and plain data, so existing sessions keep working after a key is set up and
+// are encrypted the next time they are written.
+#[derive(Clone)]
+pub struct SessionCipher {
+    cipher: ChaCha20Poly1305,
+}
+
+impl SessionCipher {
+    // A 32-byte key encoded as 64 hex characters or as base64
+    pub fn from_key_str(key: &str) -> Result<Self> {
+        let key = key.trim();
+        let bytes = hex::decode(key)
+            .ok()
+            .or_else(|| BASE64.decode(key).ok())
+            .filter(|b| b.len() == 32)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Session key must be 32 bytes, encoded as 64 hex characters or base64 (e.g. `openssl rand -hex 32`)"
+                )
+            })?;
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&bytes)),
+        })
+    }
+
+    // Key from `key_file` if given, else from the environment; None if neither is set
+    pub fn load(key_file: Option<&Path>) -> Result<Option<Self>> {
+        let key = match key_file {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| {
+                anyhow::anyhow!("Failed to read key file {}: {}", path.display(), e)
+            })?,
+            None => match std::env::var(KEY_ENV_VAR) {
+                Ok(key) if !key.trim().is_empty() => key,
+                _ => return Ok(None),
+            },
+        };
+        Self::from_key_str(&key).map(Some)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+        let mut payload = nonce.to_vec();
+        payload.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", MARKER, BASE64.encode(payload)))
+    }
+
+    fn decrypt(&self, data: &[u8], what: &str) -> Result<Vec<u8>> {
+        let wrong_key = || anyhow::anyhow!("Cannot decrypt {}: wrong key or corrupted data", what);
+        let payload = BASE64
+            .decode(data[MARKER.len()..].trim_ascii())
+            .map_err(|_| wrong_key())?;
+        if payload.len() < NONCE_LEN {
+            return Err(wrong_key());
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| wrong_key())
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MARKER.as_bytes())
+}
+
+// Plain contents of data that may be encrypted; `what` names it in errors
+pub fn decode(cipher: Option<&SessionCipher>, data: Vec<u8>, what: &str) -> Result<Vec<u8>> {
+    if !is_encrypted(&data) {
+        return Ok(data);
+    }
+    match cipher {
+        Some(cipher) => cipher.decrypt(&data, what),
+        None => Err(anyhow::anyhow!(
+            "{} is encrypted; set {} or pass --session-key-file",
+            what,
+            KEY_ENV_VAR
+        )),
+    }
+}
+
+// Data as written to disk: encrypted when a cipher is configured
+pub fn encode(cipher: Option<&SessionCipher>, data: Vec<u8>) -> Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => Ok(cipher.encrypt(&data)?.into_bytes()),
+        None => Ok(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(byte: u8) -> SessionCipher {
+        SessionCipher::from_key_str(&BASE64.encode([byte; 32])).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cipher = SessionCipher::from_key_str(&"11".repeat(32)).unwrap();
+        let encoded = encode(Some(&cipher), b"secret".to_vec()).unwrap();
+        assert!(is_encrypted(&encoded));
+        assert!(!String::from_utf8_lossy(&encoded).contains("secret"));
+        assert_eq!(decode(Some(&cipher), encoded, "x").unwrap(), b"secret");
+        // Without a cipher data is written as it is
+        assert_eq!(encode(None, b"secret".to_vec()).unwrap(), b"secret");
+
+        assert!(SessionCipher::from_key_str("too short").is_err());
+    }
+
+    #[test]
+    fn test_wrong_key() {
+        let encoded = encode(Some(&cipher(1)), b"secret".to_vec()).unwrap();
+        let err = decode(Some(&cipher(7)), encoded.clone(), "Session 'a'").unwrap_err();
+        assert!(err.to_string().contains("wrong key"), "{}", err);
+        let err = decode(None, encoded, "Session 'a'").unwrap_err();
+        assert!(err.to_string().contains(KEY_ENV_VAR), "{}", err);
+    }
+
+    #[test]
+    fn test_plaintext_passthrough() {
+        // Data written before encryption was enabled still reads with a key
+        assert_eq!(
+            decode(Some(&cipher(1)), b"{}".to_vec(), "x").unwrap(),
+            b"{}"
+        );
+        assert_eq!(decode(None, b"{}".to_vec(), "x").unwrap(), b"{}");
+    }
+}
@@ -0,0 +1,508 @@
+use super::SessionData;
+use crate::llm::{Message, Role};
+use anyhow::Result;
+use regex::Regex;
+use serde_json::{Value, json};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DatasetFormat {
+    // `{"messages": [...]}` with `tool_calls` and `tool` messages, as used for
+    // OpenAI chat fine-tuning
+    OpenAI,
+    // `{"system": ..., "messages": [...]}` with `tool_use` and `tool_result` blocks
+    Anthropic,
+}
+
+impl DatasetFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAI),
+            "anthropic" | "claude" => Ok(Self::Anthropic),
+            _ => Err(anyhow::anyhow!(
+                "Unknown dataset format '{}' (expected openai or anthropic)",
+                s
+            )),
+        }
+    }
+}
+
+// Which sessions make it into a dataset
+#[derive(Debug, Clone, Default)]
+pub struct DatasetFilter {
+    // Only sessions whose last assistant message is a final answer
+    pub require_final: bool,
+    // Only sessions with at least one of these tags; empty means any
+    pub tags: Vec<String>,
+}
+
+impl DatasetFilter {
+    pub fn matches(&self, data: &SessionData) -> bool {
+        if !self.tags.is_empty() && !self.tags.iter().any(|t| data.tags.contains(t)) {
+            return false;
+        }
+        if self.require_final {
+            let last = data
+                .messages
+                .iter()
+                .rev()
+                .find(|m| m.role == Role::Assistant);
+            // Imported sessions keep the <final> block in the message itself
+            return last.is_some_and(|m| m.metadata.final_answer || m.content.contains("<final>"));
+        }
+        true
+    }
+}
+
+// Rewrites message text before it goes into a dataset, e.g. to mask PII
+pub trait Redactor {
+    fn redact(&self, text: &str) -> String;
+}
+
+// Regex replacements. The default rules mask e-mail addresses, common API
+// key and token shapes, private keys and IP addresses.
+pub struct RegexRedactor {
+    rules: Vec<(Regex, String)>,
+}
+
+impl RegexRedactor {
+    pub fn new(rules: Vec<(Regex, String)>) -> Self {
+        Self { rules }
+    }
+
+    pub fn with_default_rules() -> Self {
+        let rules = [
+            (
+                r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----",
+                "[REDACTED_PRIVATE_KEY]",
+            ),
+            (
+                r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+                "[REDACTED_EMAIL]",
+            ),
+            (
+                r"\bsk-(?:ant-|proj-)?[A-Za-z0-9_-]{20,}",
+                "[REDACTED_SECRET]",
+            ),
+            (r"\b(?:AKIA|ASIA)[0-9A-Z]{16}\b", "[REDACTED_SECRET]"),
+            (r"\bgh[pousr]_[A-Za-z0-9]{36,}\b", "[REDACTED_SECRET]"),
+            (r"\bxox[abprs]-[A-Za-z0-9-]{10,}", "[REDACTED_SECRET]"),
+            (
+                r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]{16,}",
+                "Bearer [REDACTED_SECRET]",
+            ),
+            (
+                r#"(?i)\b(password|passwd|secret|api_key|apikey|token)(\s*[:=]\s*)[^\s"',;\\]+"#,
+                "$1$2[REDACTED_SECRET]",
+            ),
+            (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[REDACTED_IP]"),
+        ];
+        Self::new(
+            rules
+                .iter()
+                .map(|(re, to)| (Regex::new(re).unwrap(), to.to_string()))
+                .collect(),
+        )
+    }
+
+    // Extra rules from a file: one `<regex>` or `<regex>\t<replacement>` per
+    // line; blank lines and lines starting with `#` are skipped
+    pub fn from_rules_file(path: &Path) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (n, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (pattern, replacement) = line.split_once('\t').unwrap_or((line, "[REDACTED]"));
+            let re = Regex::new(pattern).map_err(|e| {
+                anyhow::anyhow!("{}:{}: invalid pattern: {}", path.display(), n + 1, e)
+            })?;
+            rules.push((re, replacement.to_string()));
+        }
+        Ok(Self::new(rules))
+    }
+}
+
+impl Redactor for RegexRedactor {
+    fn redact(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (re, replacement) in &self.rules {
+            text = re.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        text
+    }
+}
+
+// One dataset line for a session, after running every redactor over the text
+pub fn record(data: &SessionData, format: DatasetFormat, redactors: &[Box<dyn Redactor>]) -> Value {
+    let messages: Vec<Message> = data
+        .messages
+        .iter()
+        .enumerate()
+        // The CLI records the raw <final> response and then the answer taken
+        // from it; only the response the model actually gave is kept
+        .filter(|(i, m)| {
+            !(m.metadata.final_answer
+                && i.checked_sub(1)
+                    .is_some_and(|p| data.messages[p].role == Role::Assistant))
+        })
+        .map(|(_, m)| {
+            let mut m = m.clone();
+            m.content = redactors.iter().fold(m.content, |text, r| r.redact(&text));
+            m
+        })
+        .collect();
+    match format {
+        DatasetFormat::OpenAI => to_openai(&messages),
+        DatasetFormat::Anthropic => to_anthropic(&messages),
+    }
+}
+
+// Text around `<tool_code>` blocks and the calls in them. Blocks that are not
+// valid calls stay in the text.
+fn split_tool_calls(content: &str) -> (String, Vec<(String, Value)>) {
+    let re = Regex::new(r"(?s)<tool_code>\s*(.*?)\s*</tool_code>").unwrap();
+    let mut calls = Vec::new();
+    let text = re.replace_all(content, |caps: &regex::Captures| {
+        let call: Value = serde_json::from_str(&caps[1]).unwrap_or(Value::Null);
+        match call["name"].as_str() {
+            Some(name) => {
+                calls.push((name.to_string(), call["args"].clone()));
+                String::new()
+            }
+            None => caps[0].to_string(),
+        }
+    });
+    (text.trim().to_string(), calls)
+}
+
+fn to_openai(messages: &[Message]) -> Value {
+    let mut out = Vec::new();
+    let mut next_id = 1;
+    // Calls not answered yet, oldest first
+    let mut pending: Vec<(String, String)> = Vec::new();
+    for m in messages {
+        match m.role {
+            Role::System => out.push(json!({"role": "system", "content": m.content})),
+            Role::User => out.push(json!({"role": "user", "content": m.content})),
+            Role::Assistant => {
+                let (text, calls) = split_tool_calls(&m.content);
+                let mut message = json!({"role": "assistant", "content": text});
+                if !calls.is_empty() {
+                    let tool_calls: Vec<Value> = calls
+                        .into_iter()
+                        .map(|(name, args)| {
+                            let id = format!("call_{}", next_id);
+                            next_id += 1;
+                            pending.push((id.clone(), name.clone()));
+                            json!({
+                                "id": id,
+                                "type": "function",
+                                "function": {"name": name, "arguments": args.to_string()},
+                            })
+                        })
+                        .collect();
+                    message["tool_calls"] = json!(tool_calls);
+                    if text.is_empty() {
+                        message["content"] = Value::Null;
+                    }
+                }
+                out.push(message);
+            }
+            Role::Tool => {
+                let name = m.metadata.tool_name.as_deref();
+                match take_call(&mut pending, name) {
+                    Some(id) => {
+                        out.push(json!({"role": "tool", "tool_call_id": id, "content": m.content}))
+                    }
+                    // Output without a recorded call, e.g. after compression
+                    None => out.push(json!({"role": "user", "content": m.rendered_content()})),
+                }
+            }
+        }
+    }
+    json!({ "messages": out })
+}
+
+fn to_anthropic(messages: &[Message]) -> Value {
+    let thinking = Regex::new(r"(?s)<thinking>(.*?)</thinking>").unwrap();
+    let mut system = Vec::new();
+    let mut out: Vec<Value> = Vec::new();
+    let mut next_id = 1;
+    let mut pending: Vec<(String, String)> = Vec::new();
+    for m in messages {
+        let (role, blocks) = match m.role {
+            Role::System => {
+                system.push(m.content.clone());
+                continue;
+            }
+            Role::User => ("user", vec![json!({"type": "text", "text": m.content})]),
+            Role::Assistant => {
+                let (text, calls) = split_tool_calls(&m.content);
+                let mut blocks = Vec::new();
+                for caps in thinking.captures_iter(&text) {
+                    blocks.push(json!({"type": "thinking", "thinking": caps[1].trim()}));
+                }
+                let text = thinking.replace_all(&text, "").trim().to_string();
+                if !text.is_empty() {
+                    blocks.push(json!({"type": "text", "text": text}));
+                }
+                for (name, args) in calls {
+                    let id = format!("toolu_{}", next_id);
+                    next_id += 1;
+                    pending.push((id.clone(), name.clone()));
+                    blocks.push(json!({"type": "tool_use", "id": id, "name": name, "input": args}));
+                }
+                ("assistant", blocks)
+            }
+            Role::Tool => match take_call(&mut pending, m.metadata.tool_name.as_deref()) {
+                Some(id) => (
+                    "user",
+                    vec![json!({"type": "tool_result", "tool_use_id": id, "content": m.content})],
+                ),
+                None => (
+                    "user",
+                    vec![json!({"type": "text", "text": m.rendered_content()})],
+                ),
+            },
+        };
+        if blocks.is_empty() {
+            continue;
+        }
+        // The Messages API alternates roles, so consecutive turns are merged
+        match out.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(content) = last["content"].as_array_mut() {
+                    content.extend(blocks);
+                }
+            }
+            _ => out.push(json!({"role": role, "content": blocks})),
+        }
+    }
+    let mut record = json!({ "messages": out });
+    if !system.is_empty() {
+        record["system"] = json!(system.join("\n\n"));
+    }
+    record
+}
+
+// Id of the oldest pending call to `name` (or any call if the output has no name)
+fn take_call(pending: &mut Vec<(String, String)>, name: Option<&str>) -> Option<String> {
+    let i = pending
+        .iter()
+        .position(|(_, n)| name.is_none_or(|name| name == n))?;
+    Some(pending.remove(i).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SessionManager;
+    use super::super::storage::{self, DirectoryStore};
+    use super::*;
+    use crate::context::ContextManager;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn session(tags: &[&str], messages: Vec<Message>) -> SessionData {
+        SessionData {
+            version: super::super::migrate::SCHEMA_VERSION,
+            id: "s".to_string(),
+            messages,
+            created_at: String::new(),
+            updated_at: String::new(),
+            title: None,
+            provider: String::new(),
+            model: String::new(),
+            cwd: String::new(),
+            message_count: 0,
+            token_count: 0,
+            parent: None,
+            branch_point: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_dataset_records() {
+        let data = session(
+            &["good"],
+            vec![
+                Message::new(Role::System, "Be brief"),
+                Message::new(
+                    Role::User,
+                    "Mail admin@example.com the key sk-abcdefghijklmnopqrstuvwx",
+                ),
+                Message::new(
+                    Role::Assistant,
+                    "<thinking>send it</thinking>\n<tool_code>\n{\"name\": \"bash\", \"args\": {\"command\": \"mail admin@example.com\"}}\n</tool_code>",
+                ),
+                Message::tool("bash", "sent"),
+                Message::new(Role::Assistant, "<final>Done</final>"),
+            ],
+        );
+        let redactors: Vec<Box<dyn Redactor>> = vec![Box::new(RegexRedactor::with_default_rules())];
+
+        let openai = record(&data, DatasetFormat::OpenAI, &redactors);
+        let messages = openai["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 5);
+        assert_eq!(
+            messages[1]["content"],
+            "Mail [REDACTED_EMAIL] the key [REDACTED_SECRET]"
+        );
+        let call = &messages[2]["tool_calls"][0];
+        assert_eq!(call["function"]["name"], "bash");
+        let args: Value =
+            serde_json::from_str(call["function"]["arguments"].as_str().unwrap()).unwrap();
+        assert_eq!(args["command"], "mail [REDACTED_EMAIL]");
+        assert_eq!(messages[3]["role"], "tool");
+        assert_eq!(messages[3]["tool_call_id"], call["id"]);
+
+        let anthropic = record(&data, DatasetFormat::Anthropic, &[]);
+        assert_eq!(anthropic["system"], "Be brief");
+        let messages = anthropic["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        let blocks = messages[1]["content"].as_array().unwrap();
+        assert_eq!(blocks[0]["type"], "thinking");
+        assert_eq!(blocks[1]["type"], "tool_use");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], blocks[1]["id"]);
+
+        let filter = |require_final, tags: &[&str]| DatasetFilter {
+            require_final,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        assert!(filter(true, &["good", "other"]).matches(&data));
+        assert!(!filter(false, &["bad"]).matches(&data));
+        let unfinished = session(&[], vec![Message::new(Role::Assistant, "Working on it")]);
+        assert!(!filter(true, &[]).matches(&unfinished));
+        assert!(filter(false, &[]).matches(&unfinished));
+    }
+
+    #[test]
+    fn test_final_answer_exported_once() {
+        // Recorded the way the CLI loop records a completed turn
+        let data = session(
+            &[],
+            vec![
+                Message::new(Role::User, "Say hi"),
+                Message::new(
+                    Role::Assistant,
+                    "<thinking>easy</thinking>\n<final>Hi</final>",
+                ),
+                Message::final_answer("Hi"),
+            ],
+        );
+
+        let openai = record(&data, DatasetFormat::OpenAI, &[]);
+        let messages = openai["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["role"], "assistant");
+
+        let anthropic = record(&data, DatasetFormat::Anthropic, &[]);
+        let messages = anthropic["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        let blocks = messages[1]["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["type"], "thinking");
+        assert_eq!(blocks[1]["text"], "<final>Hi</final>");
+
+        // An answer without the raw response before it is kept
+        let imported = session(
+            &[],
+            vec![
+                Message::new(Role::User, "Say hi"),
+                Message::final_answer("Hi"),
+            ],
+        );
+        let openai = record(&imported, DatasetFormat::OpenAI, &[]);
+        assert_eq!(openai["messages"][1]["content"], "Hi");
+    }
+
+    #[test]
+    fn test_export_dataset() {
+        let dir = tempdir().unwrap();
+        let store = Box::new(DirectoryStore::new(dir.path().to_path_buf(), 0));
+        let manager = SessionManager::new(dir.path().to_path_buf(), store);
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::System, "You are a bot"));
+        ctx.add_message(Message::new(Role::User, "List files of admin@example.com"));
+        ctx.add_message(Message::new(
+            Role::Assistant,
+            "<tool_code>\n{\"name\": \"bash\", \"args\": {\"command\": \"ls\"}}\n</tool_code>",
+        ));
+        ctx.add_message(Message::tool("bash", "a.rs\nb.rs"));
+        manager.save_session("tagged", &ctx).unwrap();
+        manager.save_session("untagged", &ctx).unwrap();
+        manager
+            .update_session_tags("tagged", &["good".to_string()], &[])
+            .unwrap();
+        // An unreadable session is skipped instead of failing the export
+        fs::write(dir.path().join("broken.json"), "{").unwrap();
+
+        // Only tagged sessions go into a dataset when tags are given
+        let filter = DatasetFilter {
+            require_final: false,
+            tags: vec!["good".to_string()],
+        };
+        let redactors: Vec<Box<dyn Redactor>> = vec![Box::new(RegexRedactor::with_default_rules())];
+        let path = dir.path().join("dataset.jsonl");
+        let (count, skipped) = manager
+            .export_dataset(DatasetFormat::OpenAI, &filter, &redactors, &path)
+            .unwrap();
+        assert_eq!((count, skipped), (1, 1));
+        let dataset = fs::read_to_string(&path).unwrap();
+        let record: Value = serde_json::from_str(dataset.trim()).unwrap();
+        assert_eq!(
+            record["messages"][1]["content"],
+            "List files of [REDACTED_EMAIL]"
+        );
+        assert_eq!(
+            record["messages"][2]["tool_calls"][0]["function"]["name"],
+            "bash"
+        );
+
+        let (count, _) = manager
+            .export_dataset(
+                DatasetFormat::Anthropic,
+                &DatasetFilter::default(),
+                &[],
+                &path,
+            )
+            .unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_export_dataset_require_final() {
+        let dir = tempdir().unwrap();
+        let store = storage::open_store("sqlite", dir.path(), 0, None).unwrap();
+        let manager = SessionManager::new(dir.path().to_path_buf(), store);
+        // Recorded the way the CLI loop records a completed turn: the raw
+        // response, then the answer extracted from its <final> block
+        let mut done = ContextManager::new(1000);
+        done.add_message(Message::new(Role::User, "Say hi"));
+        done.add_message(Message::new(Role::Assistant, "<final>Hi</final>"));
+        done.add_message(Message::final_answer("Hi"));
+        manager.save_session("done", &done).unwrap();
+        let mut open = ContextManager::new(1000);
+        open.add_message(Message::new(Role::User, "Say hi"));
+        open.add_message(Message::new(Role::Assistant, "Thinking about it"));
+        open.add_message(Message::new(Role::User, "Continue."));
+        manager.save_session("open", &open).unwrap();
+
+        let filter = DatasetFilter {
+            require_final: true,
+            tags: Vec::new(),
+        };
+        let path = dir.path().join("dataset.jsonl");
+        let (count, skipped) = manager
+            .export_dataset(DatasetFormat::OpenAI, &filter, &[], &path)
+            .unwrap();
+        assert_eq!((count, skipped), (1, 0));
+        assert!(
+            fs::read_to_string(path)
+                .unwrap()
+                .contains("<final>Hi</final>")
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use super::SessionData;
+use crate::llm::{Message, Role};
+use anyhow::Result;
+use regex::Regex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "md" | "markdown" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(anyhow::anyhow!(
+                "Unknown export format '{}' (expected md, html or jsonl)",
+                s
+            )),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Html => "html",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+pub fn export(data: &SessionData, format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Markdown => Ok(to_markdown(data)),
+        ExportFormat::Html => Ok(to_html(data)),
+        ExportFormat::Jsonl => to_jsonl(data),
+    }
+}
+
+// Pieces of a message as rendered in exports
+enum Segment<'a> {
+    Text(&'a str),
+    Thinking(&'a str),
+    ToolCall(&'a str),
+    ToolOutput(String, &'a str),
+}
+
+fn segments(message: &Message) -> Vec<Segment<'_>> {
+    if message.role == Role::Tool {
+        let name = message
+            .metadata
+            .tool_name
+            .clone()
+            .unwrap_or_else(|| "tool".to_string());
+        return vec![Segment::ToolOutput(name, &message.content)];
+    }
+    // Sessions saved before Role::Tool carried tool output as user messages
+    if message.role == Role::User
+        && let Some(rest) = message.content.strip_prefix("Tool '")
+        && let Some((name, output)) = rest.split_once("' output:\n")
+    {
+        return vec![Segment::ToolOutput(name.to_string(), output)];
+    }
+    if message.role != Role::Assistant {
+        return vec![Segment::Text(&message.content)];
+    }
+
+    let re = Regex::new(r"(?s)<thinking>(.*?)</thinking>|<tool_code>(.*?)</tool_code>").unwrap();
+    let mut out = Vec::new();
+    let mut last_end = 0;
+    for caps in re.captures_iter(&message.content) {
+        let m = caps.get(0).unwrap();
+        let before = message.content[last_end..m.start()].trim();
+        if !before.is_empty() {
+            out.push(Segment::Text(before));
+        }
+        if let Some(thinking) = caps.get(1) {
+            out.push(Segment::Thinking(thinking.as_str().trim()));
+        } else if let Some(call) = caps.get(2) {
+            out.push(Segment::ToolCall(call.as_str().trim()));
+        }
+        last_end = m.end();
+    }
+    let rest = message.content[last_end..].trim();
+    if !rest.is_empty() {
+        out.push(Segment::Text(rest));
+    }
+    out
+}
+
+fn role_label(role: &Role) -> &'static str {
+    match role {
+        Role::User => "User",
+        Role::Assistant => "Assistant",
+        Role::System => "System",
+        Role::Tool => "Tool",
+    }
+}
+
+fn title(data: &SessionData) -> String {
+    data.title.clone().unwrap_or_else(|| data.id.clone())
+}
+
+// A code fence longer than any backtick run inside the content
+fn fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in content.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn to_markdown(data: &SessionData) -> String {
+    let mut out = format!("# {}\n\n", title(data));
+    out.push_str(&format!("- Session: `{}`\n", data.id));
+    if !data.model.is_empty() {
+        out.push_str(&format!("- Model: {}/{}\n", data.provider, data.model));
+    }
+    out.push_str(&format!("- Created: {}\n", data.created_at));
+    if !data.updated_at.is_empty() {
+        out.push_str(&format!("- Updated: {}\n", data.updated_at));
+    }
+    out.push('\n');
+
+    for message in &data.messages {
+        out.push_str(&format!("## {}\n\n", role_label(&message.role)));
+        for segment in segments(message) {
+            match segment {
+                Segment::Text(text) if message.role == Role::System => {
+                    let f = fence(text);
+                    out.push_str(&format!(
+                        "<details>\n<summary>System prompt</summary>\n\n{f}\n{}\n{f}\n\n</details>\n\n",
+                        text
+                    ));
+                }
+                Segment::Text(text) => out.push_str(&format!("{}\n\n", text)),
+                Segment::Thinking(text) => out.push_str(&format!(
+                    "<details>\n<summary>Thinking</summary>\n\n{}\n\n</details>\n\n",
+                    text
+                )),
+                Segment::ToolCall(call) => {
+                    let f = fence(call);
+                    out.push_str(&format!(
+                        "<details>\n<summary>Tool call</summary>\n\n{f}json\n{}\n{f}\n\n</details>\n\n",
+                        call
+                    ));
+                }
+                Segment::ToolOutput(name, output) => {
+                    let f = fence(output);
+                    out.push_str(&format!(
+                        "<details>\n<summary>Output of <code>{}</code></summary>\n\n{f}\n{}\n{f}\n\n</details>\n\n",
+                        escape_html(&name),
+                        output.trim_end()
+                    ));
+                }
+            }
+        }
+    }
+    out
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,Segoe UI,Helvetica,Arial,sans-serif;max-width:900px;margin:2em auto;padding:0 1em;color:#24292f;line-height:1.5}\
+.meta{color:#57606a;font-size:.9em}\
+.msg{border:1px solid #d0d7de;border-radius:6px;margin:1em 0;padding:.5em 1em}\
+.role{font-weight:600;font-size:.85em;text-transform:uppercase;color:#57606a}\
+.user{background:#f6f8fa}.assistant{background:#fff}.system{background:#fff8c5}.tool{background:#f6f8fa;border-style:dashed}\
+.text{white-space:pre-wrap}\
+details{margin:.5em 0}summary{cursor:pointer;color:#0969da}\
+.thinking{color:#57606a;font-style:italic;white-space:pre-wrap}\
+pre{background:#eaeef2;padding:.75em;border-radius:6px;overflow-x:auto;white-space:pre-wrap}";
+
+fn to_html(data: &SessionData) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape_html(&title(data))));
+    out.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", HTML_STYLE));
+    out.push_str(&format!("<h1>{}</h1>\n", escape_html(&title(data))));
+    let mut meta = format!("Session <code>{}</code>", escape_html(&data.id));
+    if !data.model.is_empty() {
+        meta.push_str(&format!(
+            " &middot; {}/{}",
+            escape_html(&data.provider),
+            escape_html(&data.model)
+        ));
+    }
+    meta.push_str(&format!(" &middot; {}", escape_html(&data.created_at)));
+    out.push_str(&format!("<p class=\"meta\">{}</p>\n", meta));
+
+    for message in &data.messages {
+        let class = role_label(&message.role).to_lowercase();
+        out.push_str(&format!(
+            "<div class=\"msg {}\">\n<div class=\"role\">{}</div>\n",
+            class,
+            role_label(&message.role)
+        ));
+        for segment in segments(message) {
+            match segment {
+                Segment::Text(text) if message.role == Role::System => out.push_str(&format!(
+                    "<details><summary>System prompt</summary><pre>{}</pre></details>\n",
+                    escape_html(text)
+                )),
+                Segment::Text(text) => out.push_str(&format!(
+                    "<div class=\"text\">{}</div>\n",
+                    escape_html(text)
+                )),
+                Segment::Thinking(text) => out.push_str(&format!(
+                    "<details><summary>Thinking</summary><div class=\"thinking\">{}</div></details>\n",
+                    escape_html(text)
+                )),
+                Segment::ToolCall(call) => out.push_str(&format!(
+                    "<details><summary>Tool call</summary><pre>{}</pre></details>\n",
+                    escape_html(call)
+                )),
+                Segment::ToolOutput(name, output) => out.push_str(&format!(
+                    "<details><summary>Output of <code>{}</code></summary><pre>{}</pre></details>\n",
+                    escape_html(&name),
+                    escape_html(output.trim_end())
+                )),
+            }
+        }
+        out.push_str("</div>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// One raw message per line
+fn to_jsonl(data: &SessionData) -> Result<String> {
+    let mut out = String::new();
+    for message in &data.messages {
+        out.push_str(&serde_json::to_string(message)?);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(messages: Vec<Message>) -> SessionData {
+        SessionData {
+            version: super::super::migrate::SCHEMA_VERSION,
+            id: "export_me".to_string(),
+            messages,
+            created_at: String::new(),
+            updated_at: String::new(),
+            title: None,
+            provider: String::new(),
+            model: String::new(),
+            cwd: String::new(),
+            message_count: 0,
+            token_count: 0,
+            parent: None,
+            branch_point: None,
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_export_session() {
+        let data = session(vec![
+            Message::new(Role::System, "You are a bot"),
+            Message::new(Role::User, "List <files>"),
+            Message::new(
+                Role::Assistant,
+                "<thinking>\nuse ls\n</thinking>\nLet me look.\n<tool_code>\n{\"name\": \"bash\", \"args\": {\"command\": \"ls\"}}\n</tool_code>",
+            ),
+            Message::tool("bash", "a.rs\nb.rs"),
+        ]);
+
+        let md = export(&data, ExportFormat::Markdown).unwrap();
+        assert!(md.contains("<summary>Thinking</summary>\n\nuse ls\n"));
+        assert!(md.contains("Let me look.\n"));
+        assert!(
+            md.contains("<summary>Output of <code>bash</code></summary>\n\n```\na.rs\nb.rs\n```")
+        );
+
+        let html = export(&data, ExportFormat::Html).unwrap();
+        assert!(html.contains("List &lt;files&gt;"));
+        assert!(html.contains("<details><summary>Tool call</summary>"));
+
+        let jsonl = export(&data, ExportFormat::Jsonl).unwrap();
+        assert_eq!(jsonl.lines().count(), 4);
+        let last: Message = serde_json::from_str(jsonl.lines().last().unwrap()).unwrap();
+        assert_eq!(last.role, Role::Tool);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::llm::{Message, Role};
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    // Chat-completions `messages` arrays, with `tool_calls` and `tool` messages
+    OpenAI,
+    // Messages API transcripts, with `tool_use` and `tool_result` blocks
+    Anthropic,
+    // `{"conversations": [{"from": "human", "value": ...}]}`
+    ShareGpt,
+}
+
+impl ImportFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(Self::OpenAI),
+            "anthropic" | "claude" => Ok(Self::Anthropic),
+            "sharegpt" => Ok(Self::ShareGpt),
+            _ => Err(anyhow::anyhow!(
+                "Unknown import format '{}' (expected openai, anthropic or sharegpt)",
+                s
+            )),
+        }
+    }
+
+    // Guess the format from the structure of the document
+    pub fn detect(value: &Value) -> Result<Self> {
+        let first = value.as_array().and_then(|a| a.first()).unwrap_or(value);
+        if first.get("conversations").is_some() || first.get("from").is_some() {
+            return Ok(Self::ShareGpt);
+        }
+        let messages = messages_of(value)
+            .ok_or_else(|| anyhow::anyhow!("No messages found in the imported document"))?;
+        let anthropic_blocks = messages.iter().any(|m| {
+            m["content"].as_array().is_some_and(|blocks| {
+                blocks.iter().any(|b| {
+                    matches!(
+                        b["type"].as_str(),
+                        Some("tool_use" | "tool_result" | "thinking")
+                    )
+                })
+            })
+        });
+        if anthropic_blocks || value.get("system").is_some() {
+            Ok(Self::Anthropic)
+        } else {
+            Ok(Self::OpenAI)
+        }
+    }
+}
+
+// Conversations in the document, each as our messages. ShareGPT files may
+// hold several conversations; the other formats hold one.
+pub fn import(value: &Value, format: ImportFormat) -> Result<Vec<Vec<Message>>> {
+    match format {
+        ImportFormat::OpenAI => Ok(vec![from_openai(value)?]),
+        ImportFormat::Anthropic => Ok(vec![from_anthropic(value)?]),
+        ImportFormat::ShareGpt => from_sharegpt(value),
+    }
+}
+
+// A bare array of messages or an object (request or export) with `messages`
+fn messages_of(value: &Value) -> Option<&Vec<Value>> {
+    value
+        .as_array()
+        .filter(|a| a.iter().all(|m| m.get("role").is_some()))
+        .or_else(|| value["messages"].as_array())
+}
+
+// Tool call in the `<tool_code>` form the agent itself produces
+fn tool_code(name: &str, args: &Value) -> String {
+    let call = json!({"name": name, "args": args});
+    format!(
+        "<tool_code>\n{}\n</tool_code>",
+        serde_json::to_string_pretty(&call).unwrap_or_default()
+    )
+}
+
+// Arguments given as a JSON-encoded string are decoded; anything else is kept
+fn parse_arguments(arguments: &Value) -> Value {
+    match arguments {
+        Value::String(s) => serde_json::from_str(s).unwrap_or_else(|_| json!(s)),
+        Value::Null => json!({}),
+        other => other.clone(),
+    }
+}
+
+// Text of a string or an array of content parts; non-text parts become placeholders
+fn text_of(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part["type"].as_str() {
+                Some("text") | Some("input_text") | Some("output_text") => {
+                    part["text"].as_str().map(String::from)
+                }
+                Some(kind) => Some(format!("[{}]", kind)),
+                None => part.as_str().map(String::from),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn join_nonempty(parts: Vec<String>) -> String {
+    parts
+        .into_iter()
+        .filter(|p| !p.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn from_openai(value: &Value) -> Result<Vec<Message>> {
+    let raw = messages_of(value)
+        .ok_or_else(|| anyhow::anyhow!("Expected an array of chat-completions messages"))?;
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    let mut messages = Vec::new();
+    for m in raw {
+        let content = text_of(&m["content"]);
+        match m["role"].as_str().unwrap_or_default() {
+            "system" | "developer" => messages.push(Message::new(Role::System, content)),
+            "user" => messages.push(Message::new(Role::User, content)),
+            "assistant" => {
+                let mut parts = vec![content];
+                for call in m["tool_calls"].as_array().into_iter().flatten() {
+                    let name = call["function"]["name"].as_str().unwrap_or("tool");
+                    if let Some(id) = call["id"].as_str() {
+                        tool_names.insert(id.to_string(), name.to_string());
+                    }
+                    parts.push(tool_code(
+                        name,
+                        &parse_arguments(&call["function"]["arguments"]),
+                    ));
+                }
+                // Deprecated single function call
+                if let Some(name) = m["function_call"]["name"].as_str() {
+                    parts.push(tool_code(
+                        name,
+                        &parse_arguments(&m["function_call"]["arguments"]),
+                    ));
+                }
+                messages.push(Message::new(Role::Assistant, join_nonempty(parts)));
+            }
+            "tool" => {
+                let name = m["tool_call_id"]
+                    .as_str()
+                    .and_then(|id| tool_names.get(id))
+                    .map(String::as_str)
+                    .or(m["name"].as_str())
+                    .unwrap_or("tool");
+                messages.push(Message::tool(name, content));
+            }
+            "function" => {
+                messages.push(Message::tool(m["name"].as_str().unwrap_or("tool"), content))
+            }
+            other => return Err(anyhow::anyhow!("Unknown OpenAI message role: {}", other)),
+        }
+    }
+    Ok(messages)
+}
+
+fn from_anthropic(value: &Value) -> Result<Vec<Message>> {
+    let raw = messages_of(value)
+        .ok_or_else(|| anyhow::anyhow!("Expected an Anthropic Messages transcript"))?;
+    let mut messages = Vec::new();
+    let system = text_of(&value["system"]);
+    if !system.is_empty() {
+        messages.push(Message::new(Role::System, system));
+    }
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    for m in raw {
+        let role = match m["role"].as_str().unwrap_or_default() {
+            "user" => Role::User,
+            "assistant" => Role::Assistant,
+            other => return Err(anyhow::anyhow!("Unknown Anthropic message role: {}", other)),
+        };
+        let blocks = match &m["content"] {
+            Value::String(s) => vec![json!({"type": "text", "text": s})],
+            Value::Array(blocks) => blocks.clone(),
+            _ => Vec::new(),
+        };
+        let mut parts = Vec::new();
+        for block in &blocks {
+            match block["type"].as_str().unwrap_or_default() {
+                "text" => parts.push(block["text"].as_str().unwrap_or_default().to_string()),
+                "thinking" => parts.push(format!(
+                    "<thinking>\n{}\n</thinking>",
+                    block["thinking"].as_str().unwrap_or_default()
+                )),
+                "redacted_thinking" => {}
+                "tool_use" => {
+                    let name = block["name"].as_str().unwrap_or("tool");
+                    if let Some(id) = block["id"].as_str() {
+                        tool_names.insert(id.to_string(), name.to_string());
+                    }
+                    parts.push(tool_code(name, &block["input"]));
+                }
+                // Tool results become tool messages, after any text before them
+                "tool_result" => {
+                    let text = join_nonempty(std::mem::take(&mut parts));
+                    if !text.is_empty() {
+                        messages.push(Message::new(role.clone(), text));
+                    }
+                    let name = block["tool_use_id"]
+                        .as_str()
+                        .and_then(|id| tool_names.get(id))
+                        .map(String::as_str)
+                        .unwrap_or("tool");
+                    let mut output = text_of(&block["content"]);
+                    if block["is_error"].as_bool() == Some(true) {
+                        output = format!("Error: {}", output);
+                    }
+                    messages.push(Message::tool(name, output));
+                }
+                other => parts.push(format!("[{}]", other)),
+            }
+        }
+        let text = join_nonempty(parts);
+        if !text.is_empty() {
+            messages.push(Message::new(role, text));
+        }
+    }
+    Ok(messages)
+}
+
+fn from_sharegpt(value: &Value) -> Result<Vec<Vec<Message>>> {
+    let conversations: Vec<&Value> = match value {
+        Value::Array(items) if items.iter().any(|i| i.get("conversations").is_some()) => {
+            items.iter().map(|i| &i["conversations"]).collect()
+        }
+        // A single conversation given as the bare turn list
+        Value::Array(_) => vec![value],
+        _ => vec![&value["conversations"]],
+    };
+    let mut result = Vec::new();
+    for conversation in conversations {
+        let turns = conversation
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Expected a ShareGPT `conversations` array"))?;
+        let mut messages = Vec::new();
+        let mut last_tool = "tool".to_string();
+        for turn in turns {
+            let text = text_of(&turn["value"]);
+            match turn["from"].as_str().unwrap_or_default() {
+                "system" => messages.push(Message::new(Role::System, text)),
+                "human" | "user" => messages.push(Message::new(Role::User, text)),
+                "gpt" | "assistant" | "chatgpt" | "bard" | "bing" => {
+                    messages.push(Message::new(Role::Assistant, text))
+                }
+                // The value is a JSON call like {"name": ..., "arguments": ...}
+                "function_call" | "tool_call" => {
+                    let call: Value = serde_json::from_str(&text).unwrap_or(Value::Null);
+                    match call["name"].as_str() {
+                        Some(name) => {
+                            last_tool = name.to_string();
+                            let args = if call.get("arguments").is_some() {
+                                parse_arguments(&call["arguments"])
+                            } else {
+                                parse_arguments(&call["args"])
+                            };
+                            messages.push(Message::new(Role::Assistant, tool_code(name, &args)));
+                        }
+                        None => messages.push(Message::new(Role::Assistant, text)),
+                    }
+                }
+                "observation" | "tool" | "function_response" => {
+                    messages.push(Message::tool(&last_tool, text))
+                }
+                other => return Err(anyhow::anyhow!("Unknown ShareGPT speaker: {}", other)),
+            }
+        }
+        result.push(messages);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import_fixture(json: &str) -> Vec<Message> {
+        let value: Value = serde_json::from_str(json).unwrap();
+        let format = ImportFormat::detect(&value).unwrap();
+        import(&value, format).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_import_formats() {
+        let openai = import_fixture(
+            r#"[
+                {"role": "system", "content": "Be brief"},
+                {"role": "user", "content": [{"type": "text", "text": "List files"}]},
+                {"role": "assistant", "content": null, "tool_calls": [{"id": "call_1", "type": "function",
+                    "function": {"name": "bash", "arguments": "{\"command\": \"ls\"}"}}]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "a.rs"},
+                {"role": "assistant", "content": "One file"}
+            ]"#,
+        );
+        let anthropic = import_fixture(
+            r#"{"system": "Be brief", "messages": [
+                {"role": "user", "content": "List files"},
+                {"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": "use ls"},
+                    {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {"command": "ls"}}]},
+                {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "toolu_1",
+                    "content": [{"type": "text", "text": "a.rs"}]}]},
+                {"role": "assistant", "content": "One file"}
+            ]}"#,
+        );
+        let sharegpt = import_fixture(
+            r#"{"conversations": [
+                {"from": "system", "value": "Be brief"},
+                {"from": "human", "value": "List files"},
+                {"from": "function_call", "value": "{\"name\": \"bash\", \"arguments\": {\"command\": \"ls\"}}"},
+                {"from": "observation", "value": "a.rs"},
+                {"from": "gpt", "value": "One file"}
+            ]}"#,
+        );
+        for messages in [&openai, &anthropic, &sharegpt] {
+            let roles: Vec<Role> = messages.iter().map(|m| m.role.clone()).collect();
+            assert_eq!(
+                roles,
+                [
+                    Role::System,
+                    Role::User,
+                    Role::Assistant,
+                    Role::Tool,
+                    Role::Assistant
+                ]
+            );
+            assert!(messages[2].content.contains("<tool_code>"));
+            assert!(messages[2].content.contains("\"command\": \"ls\""));
+            assert_eq!(messages[3].metadata.tool_name.as_deref(), Some("bash"));
+            assert_eq!(messages[3].content, "a.rs");
+            assert_eq!(messages[4].content, "One file");
+        }
+        assert!(
+            anthropic[2]
+                .content
+                .starts_with("<thinking>\nuse ls\n</thinking>")
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::llm::estimate_tokens;
+use anyhow::Result;
+use serde_json::{Value, json};
+
+// Version written to new session files. Bump it together with a new entry in
+// MIGRATIONS whenever the on-disk shape of SessionData or Message changes.
+//
+// 1: `{id, created_at, messages: [{role, content}]}`; tool output stored as
+//    user messages starting with "Tool '<name>' output:"
+// 2: per-message metadata, a `tool` role and session metadata (title,
+//    model, counts, branches), but no version field
+// 3: explicit `version` field
+pub const SCHEMA_VERSION: u64 = 3;
+
+type Migration = fn(&mut Value) -> Result<()>;
+
+// Migration from version `n` to `n + 1`, in order
+const MIGRATIONS: &[(u64, Migration)] = &[(1, v1_to_v2), (2, v2_to_v3)];
+
+// Upgrade a session file of any known version to SCHEMA_VERSION
+pub fn migrate(mut value: Value) -> Result<Value> {
+    let mut version = detect_version(&value)?;
+    if version > SCHEMA_VERSION {
+        return Err(anyhow::anyhow!(
+            "Session has schema version {}, but this build only supports up to {}; upgrade mini-agent to open it",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+    for (from, migration) in MIGRATIONS {
+        if *from == version {
+            migration(&mut value)?;
+            version += 1;
+            log::debug!("Migrated session to schema version {}", version);
+        }
+    }
+    Ok(value)
+}
+
+fn detect_version(value: &Value) -> Result<u64> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("Session file is not a JSON object"))?;
+    if let Some(version) = object.get("version") {
+        return version
+            .as_u64()
+            .ok_or_else(|| anyhow::anyhow!("Invalid session version: {}", version));
+    }
+    // Unversioned files are told apart by whether messages carry metadata
+    let has_metadata = object
+        .get("messages")
+        .and_then(|m| m.as_array())
+        .is_some_and(|m| m.iter().any(|m| m.get("metadata").is_some()));
+    Ok(if has_metadata || object.contains_key("updated_at") {
+        2
+    } else {
+        1
+    })
+}
+
+fn v1_to_v2(value: &mut Value) -> Result<()> {
+    let created_at = value["created_at"].as_str().unwrap_or_default().to_string();
+    let session_id = value["id"].as_str().unwrap_or_default().to_string();
+    let messages = value["messages"]
+        .as_array_mut()
+        .ok_or_else(|| anyhow::anyhow!("Session has no messages array"))?;
+    for (index, message) in messages.iter_mut().enumerate() {
+        let mut content = message["content"].as_str().unwrap_or_default().to_string();
+        let mut tool_name = None;
+        if message["role"] == "user"
+            && let Some(rest) = content.strip_prefix("Tool '")
+            && let Some((name, output)) = rest.split_once("' output:\n")
+        {
+            tool_name = Some(name.to_string());
+            content = output.to_string();
+        }
+        let mut metadata = json!({
+            "id": migrated_message_id(&session_id, index),
+            "timestamp": created_at,
+            "token_count": estimate_tokens(&content),
+        });
+        if let Some(name) = tool_name {
+            metadata["tool_name"] = json!(name);
+            message["role"] = json!("tool");
+        }
+        message["content"] = json!(content);
+        message["metadata"] = metadata;
+    }
+    let count = messages.len();
+    let tokens: usize = messages
+        .iter()
+        .map(|m| m["metadata"]["token_count"].as_u64().unwrap_or_default() as usize)
+        .sum();
+    value["updated_at"] = json!(created_at);
+    value["message_count"] = json!(count);
+    value["token_count"] = json!(tokens);
+    Ok(())
+}
+
+// Migrations are not written back, so ids given to old messages must come
+// out the same on every load; otherwise a resumed session would autosave its
+// whole history again under new ids
+fn migrated_message_id(session_id: &str, index: usize) -> String {
+    let key = format!("{}:{}", session_id, index);
+    uuid::Uuid::from_u64_pair(fnv1a(key.as_bytes(), 0), fnv1a(key.as_bytes(), 1)).to_string()
+}
+
+// 64-bit FNV-1a, stable across builds unlike std's DefaultHasher
+fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn v2_to_v3(value: &mut Value) -> Result<()> {
+    value["version"] = json!(3);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::Role;
+    use crate::session::SessionData;
+
+    fn load(fixture: &str) -> SessionData {
+        let value: Value = serde_json::from_str(fixture).unwrap();
+        serde_json::from_value(migrate(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_migrate_fixtures() {
+        let v1 = load(include_str!("fixtures/session_v1.json"));
+        let v2 = load(include_str!("fixtures/session_v2.json"));
+        let v3 = load(include_str!("fixtures/session_v3.json"));
+        for data in [&v1, &v2, &v3] {
+            assert_eq!(data.version, SCHEMA_VERSION);
+            assert_eq!(data.messages.len(), 4);
+            assert_eq!(data.messages[3].role, Role::Tool);
+            assert_eq!(data.messages[3].metadata.tool_name.as_deref(), Some("bash"));
+            assert_eq!(data.messages[3].content, "Cargo.toml\nsrc");
+            assert!(!data.messages[0].metadata.id.is_empty());
+        }
+        // Version 1 had no update time or counts; they are derived
+        assert_eq!(v1.updated_at, v1.created_at);
+        assert_eq!(v1.message_count, 4);
+        assert_eq!(v1.messages[1].metadata.timestamp, v1.created_at);
+        assert_eq!(v2.title.as_deref(), Some("List the project"));
+        // Ids given to version 1 messages are the same on every load
+        let again = load(include_str!("fixtures/session_v1.json"));
+        let ids: Vec<&str> = v1.messages.iter().map(|m| m.metadata.id.as_str()).collect();
+        let ids_again: Vec<&str> = again
+            .messages
+            .iter()
+            .map(|m| m.metadata.id.as_str())
+            .collect();
+        assert_eq!(ids, ids_again);
+        assert!(ids.windows(2).all(|w| w[0] != w[1]));
+
+        let future = serde_json::json!({"version": SCHEMA_VERSION + 1, "id": "x", "messages": []});
+        let err = migrate(future).unwrap_err().to_string();
+        assert!(err.contains("upgrade mini-agent"), "{}", err);
+    }
+}
@@ -1,84 +1,887 @@
+pub mod crypto;
+pub mod dataset;
+pub mod export;
+pub mod import;
+pub mod migrate;
+pub mod retention;
+pub mod search;
+pub mod sqlite;
+pub mod storage;
+pub mod transcript;
+
 use crate::context::ContextManager;
-use crate::llm::Message;
+use crate::llm::{LLM, Message, Role, estimate_tokens};
 use anyhow::Result;
+use crypto::SessionCipher;
+use dataset::{DatasetFilter, DatasetFormat, Redactor};
+use export::ExportFormat;
+use import::ImportFormat;
+use migrate::SCHEMA_VERSION;
+use retention::RetentionPolicy;
+use search::{SearchHit, search_files};
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::Path;
 use std::path::PathBuf;
+use storage::SessionStore;
+use transcript::{TranscriptWriter, generate_session_id, read_transcript, transcript_path};
+
+// Autosaved per-session transcripts live in this subdirectory of the storage dir
+const TRANSCRIPT_DIR: &str = "transcripts";
+// Spilled tool outputs, one subdirectory per autosaved session
+const SPILL_DIR: &str = "spill";
+// Search index over autosaved transcripts that were never saved
+const TRANSCRIPT_INDEX_FILE: &str = "index/transcripts.json";
+const MAX_SESSION_NAME_CHARS: usize = 128;
 
 #[derive(Serialize, Deserialize)]
 pub struct SessionData {
+    // On-disk schema version; older files are upgraded by `migrate` on load
+    #[serde(default)]
+    pub version: u64,
     pub id: String,
     pub messages: Vec<Message>,
     pub created_at: String,
+    #[serde(default)]
+    pub updated_at: String,
+    // Short LLM-generated description of the conversation
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub provider: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub cwd: String,
+    #[serde(default)]
+    pub message_count: usize,
+    #[serde(default)]
+    pub token_count: usize,
+    // Session this one was forked from, forming a tree of branches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    // Number of leading messages shared with the parent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_point: Option<usize>,
+    // Free-form labels, e.g. for selecting sessions for a dataset
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+// A session in a branch tree, in depth-first order
+pub struct BranchNode {
+    pub id: String,
+    pub depth: usize,
+    pub messages: usize,
+    pub branch_point: Option<usize>,
+    pub title: Option<String>,
+}
+
+// A saved session or autosaved transcript, as shown in listings and pickers
+pub struct SessionEntry {
+    pub id: String,
+    pub title: Option<String>,
+    pub provider: String,
+    pub model: String,
+    pub cwd: String,
+    // Last activity (RFC 3339)
+    pub updated_at: String,
+    pub messages: usize,
+    pub tokens: usize,
+    // Size on disk with backups, transcript and spilled outputs, for retention limits
+    pub bytes: u64,
+    // Only available as an autosaved transcript, never saved with /save
+    pub autosaved: bool,
+}
+
+impl SessionEntry {
+    // Last activity in local time, for display
+    pub fn updated_display(&self) -> String {
+        chrono::DateTime::parse_from_rfc3339(&self.updated_at)
+            .map(|t| {
+                t.with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+            })
+            .unwrap_or_else(|_| "-".to_string())
+    }
 }
 
 pub struct SessionManager {
     storage_dir: PathBuf,
+    // Saved sessions; transcripts are always files under storage_dir
+    store: Box<dyn SessionStore>,
+    transcript: Option<TranscriptWriter>,
+    // Encrypts transcripts; the store gets its own copy
+    cipher: Option<SessionCipher>,
+    provider: String,
+    model: String,
 }
 
 impl SessionManager {
-    pub fn new(storage_dir: PathBuf) -> Self {
+    pub fn new(storage_dir: PathBuf, store: Box<dyn SessionStore>) -> Self {
         if !storage_dir.exists() {
             fs::create_dir_all(&storage_dir).unwrap_or_default();
         }
-        Self { storage_dir }
+        Self {
+            storage_dir,
+            store,
+            transcript: None,
+            cipher: None,
+            provider: String::new(),
+            model: String::new(),
+        }
+    }
+
+    pub fn set_cipher(&mut self, cipher: Option<SessionCipher>) {
+        self.cipher = cipher;
+    }
+
+    pub fn cipher(&self) -> Option<&SessionCipher> {
+        self.cipher.as_ref()
+    }
+
+    // Provider and model recorded in saved sessions
+    pub fn set_model_info(&mut self, provider: &str, model: &str) {
+        self.provider = provider.to_string();
+        self.model = model.to_string();
+    }
+
+    // Autosave into a new transcript, or into the existing one of `resume`
+    pub fn start_autosave(&mut self, resume: Option<&str>) -> Result<String> {
+        let id = match resume {
+            Some(id) => {
+                validate_session_name(id)?;
+                id.to_string()
+            }
+            None => generate_session_id(),
+        };
+        let writer = TranscriptWriter::create(
+            &self.storage_dir.join(TRANSCRIPT_DIR),
+            &id,
+            self.cipher.clone(),
+        )?;
+        log::info!("Autosaving session to {}", writer.path().display());
+        self.transcript = Some(writer);
+        Ok(id)
+    }
+
+    pub fn current_session_id(&self) -> Option<&str> {
+        self.transcript.as_ref().map(|t| t.session_id())
+    }
+
+    // Where oversized tool outputs of a session are kept
+    pub fn spill_dir(&self, session_id: &str) -> PathBuf {
+        self.storage_dir.join(SPILL_DIR).join(session_id)
+    }
+
+    // Delete a saved session together with its transcript and spilled outputs
+    pub fn delete_session(&self, session_id: &str) -> Result<()> {
+        if Some(session_id) == self.current_session_id() {
+            return Err(anyhow::anyhow!(
+                "Session '{}' is in use by this process",
+                session_id
+            ));
+        }
+        let mut found = self.store.delete(session_id)?;
+        let transcript = self.transcript_file(session_id)?;
+        if transcript.exists() {
+            fs::remove_file(transcript)?;
+            found = true;
+        }
+        let spill = self.spill_dir(session_id);
+        if spill.exists() {
+            fs::remove_dir_all(spill)?;
+        }
+        if !found {
+            return Err(anyhow::anyhow!("Session '{}' not found", session_id));
+        }
+        Ok(())
+    }
+
+    // Give a session a new id, together with its transcript and spilled
+    // outputs. Branches forked from it follow the rename.
+    pub fn rename_session(&mut self, from: &str, to: &str) -> Result<()> {
+        validate_session_name(to)?;
+        if self.store.exists(to)? || self.transcript_file(to)?.exists() {
+            return Err(anyhow::anyhow!("Session '{}' already exists", to));
+        }
+        let transcript = self.transcript_file(from)?;
+        let current = Some(from) == self.current_session_id();
+        let saved = self.store.exists(from)?;
+        // Unsaved sessions only have a transcript; the current one is still being written
+        if !saved && (current || !transcript.exists()) {
+            return Err(anyhow::anyhow!("Session '{}' not found", from));
+        }
+        if saved {
+            self.store.rename(from, to)?;
+        }
+        if transcript.exists() {
+            fs::rename(transcript, self.transcript_file(to)?)?;
+        }
+        if self.spill_dir(from).exists() {
+            fs::rename(self.spill_dir(from), self.spill_dir(to))?;
+        }
+        // A resumed session keeps autosaving under its new id
+        if current {
+            self.start_autosave(Some(to))?;
+        }
+        for id in self.store.list()? {
+            let Ok(mut data) = self.store.read(&id) else {
+                continue;
+            };
+            if data.parent.as_deref() == Some(from) {
+                data.parent = Some(to.to_string());
+                self.store.write(&data)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Delete sessions beyond the policy's limits, oldest first. The current
+    // session and `keep` (e.g. one about to be resumed) are never candidates.
+    // With `dry_run` nothing is deleted. Returns the affected ids; a session
+    // that cannot be deleted is logged and left out.
+    pub fn prune(
+        &self,
+        policy: &RetentionPolicy,
+        dry_run: bool,
+        keep: Option<&str>,
+    ) -> Result<Vec<String>> {
+        let mut entries = self.list_recent_sessions()?;
+        entries.retain(|e| {
+            Some(e.id.as_str()) != keep && Some(e.id.as_str()) != self.current_session_id()
+        });
+        let expired = policy.expired(&entries, chrono::Utc::now());
+        if dry_run {
+            return Ok(expired);
+        }
+        let mut deleted = Vec::new();
+        for id in expired {
+            match self.delete_session(&id) {
+                Ok(()) => deleted.push(id),
+                Err(e) => log::warn!("Failed to prune session {}: {}", id, e),
+            }
+        }
+        Ok(deleted)
+    }
+
+    // Append messages added to the context since the last call
+    pub fn autosave(&mut self, context: &ContextManager) -> Result<()> {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.append_new(context.get_history())?;
+        }
+        Ok(())
+    }
+
+    // Record a rewind in the transcript, so recovering or resuming the
+    // session does not bring back the discarded messages
+    pub fn record_rewind(&mut self, context: &ContextManager) -> Result<()> {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.rewind(context.get_history())?;
+        }
+        Ok(())
+    }
+
+    // Record that compression rewrote the context, so recovering the session
+    // gives the compressed conversation rather than the full one plus a summary
+    pub fn record_compression(&mut self, context: &ContextManager) -> Result<()> {
+        if let Some(transcript) = self.transcript.as_mut() {
+            transcript.replace(context.get_history())?;
+        }
+        Ok(())
+    }
+
+    // Autosaved transcripts other than the current one, most recent first
+    pub fn list_transcripts(&self) -> Result<Vec<SessionEntry>> {
+        let dir = self.storage_dir.join(TRANSCRIPT_DIR);
+        let mut transcripts = Vec::new();
+        if !dir.exists() {
+            return Ok(transcripts);
+        }
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Some(id) = name.strip_suffix(".jsonl") else {
+                continue;
+            };
+            if Some(id) == self.current_session_id() {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let messages = match read_transcript(&entry.path(), self.cipher.as_ref()) {
+                Ok(messages) => messages,
+                Err(e) => {
+                    log::warn!("Skipping unreadable transcript {}: {}", id, e);
+                    continue;
+                }
+            };
+            transcripts.push(SessionEntry {
+                id: id.to_string(),
+                title: None,
+                provider: String::new(),
+                model: String::new(),
+                cwd: String::new(),
+                updated_at: modified_at(&entry.path())?,
+                tokens: messages.iter().map(|m| estimate_tokens(&m.content)).sum(),
+                messages: messages.len(),
+                bytes: metadata.len(),
+                autosaved: true,
+            });
+        }
+        transcripts.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(transcripts)
+    }
+
+    pub fn recover_transcript(&self, session_id: &str, context: &mut ContextManager) -> Result<()> {
+        let path = self.transcript_file(session_id)?;
+        if !path.exists() {
+            return Err(anyhow::anyhow!(
+                "No transcript for session '{}'",
+                session_id
+            ));
+        }
+        context.load_history(read_transcript(&path, self.cipher.as_ref())?);
+        Ok(())
     }
 
     pub fn save_session(&self, session_id: &str, context: &ContextManager) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        // Re-saving keeps the creation time and title of the existing session
+        let previous = self.read_session_data(session_id).ok();
+        let messages = context.get_history().to_vec();
         let data = SessionData {
+            version: SCHEMA_VERSION,
             id: session_id.to_string(),
-            messages: context.get_history().to_vec(),
-            created_at: chrono::Utc::now().to_rfc3339(),
+            created_at: previous
+                .as_ref()
+                .map(|p| p.created_at.clone())
+                .unwrap_or_else(|| now.clone()),
+            updated_at: now,
+            title: previous.as_ref().and_then(|p| p.title.clone()),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            cwd: std::env::current_dir()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            message_count: messages.len(),
+            token_count: context.total_tokens(),
+            parent: previous.as_ref().and_then(|p| p.parent.clone()),
+            branch_point: previous.as_ref().and_then(|p| p.branch_point),
+            tags: previous.map(|p| p.tags).unwrap_or_default(),
+            messages,
         };
-        let path = self.storage_dir.join(format!("{}.json", session_id));
-        let file = fs::File::create(path)?;
-        serde_json::to_writer_pretty(file, &data)?;
+        self.write_session_data(&data)?;
+        self.copy_spilled_outputs(session_id)
+    }
+
+    // Saved sessions keep their own copy of the outputs spilled while they
+    // were autosaved under another id, so handles resolve after loading them
+    fn copy_spilled_outputs(&self, session_id: &str) -> Result<()> {
+        let Some(current) = self.current_session_id() else {
+            return Ok(());
+        };
+        let from = self.spill_dir(current);
+        if current == session_id || !from.exists() {
+            return Ok(());
+        }
+        let to = self.spill_dir(session_id);
+        fs::create_dir_all(&to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+            if !target.exists() {
+                fs::copy(entry.path(), target)?;
+            }
+        }
         Ok(())
     }
 
+    // Save the context as a new session branching off `parent`
+    pub fn fork_session(
+        &self,
+        session_id: &str,
+        parent: Option<&str>,
+        context: &ContextManager,
+    ) -> Result<()> {
+        if self.read_session_data(session_id).is_ok() {
+            return Err(anyhow::anyhow!("Session '{}' already exists", session_id));
+        }
+        self.save_session(session_id, context)?;
+        let Some(parent) = parent else {
+            return Ok(());
+        };
+        let parent_data = self.read_session_data(parent)?;
+        let mut data = self.read_session_data(session_id)?;
+        // Count the leading messages both branches share
+        let shared = parent_data
+            .messages
+            .iter()
+            .zip(&data.messages)
+            .take_while(|(a, b)| a.metadata.id == b.metadata.id && a.content == b.content)
+            .count();
+        data.parent = Some(parent.to_string());
+        data.branch_point = Some(shared);
+        data.title = parent_data.title;
+        data.tags = parent_data.tags;
+        self.write_session_data(&data)
+    }
+
+    // First free id of the form `<base>-<n>` for an automatic branch
+    pub fn next_branch_id(&self, base: &str) -> String {
+        let sessions = self.list_sessions().unwrap_or_default();
+        (1..)
+            .map(|n| format!("{}-{}", base, n))
+            .find(|id| !sessions.contains(id))
+            .unwrap_or_else(|| base.to_string())
+    }
+
+    // The whole branch tree that `session_id` belongs to, starting at its root
+    pub fn list_branches(&self, session_id: &str) -> Result<Vec<BranchNode>> {
+        let mut sessions = Vec::new();
+        for id in self.list_sessions()? {
+            if let Ok(data) = self.read_session_data(&id) {
+                sessions.push(data);
+            }
+        }
+        let mut root = session_id.to_string();
+        let mut seen = vec![root.clone()];
+        while let Some(parent) = sessions
+            .iter()
+            .find(|s| s.id == root)
+            .and_then(|s| s.parent.clone())
+        {
+            if seen.contains(&parent) || !sessions.iter().any(|s| s.id == parent) {
+                break;
+            }
+            seen.push(parent.clone());
+            root = parent;
+        }
+        if !sessions.iter().any(|s| s.id == root) {
+            return Err(anyhow::anyhow!("Session '{}' not found", session_id));
+        }
+
+        let mut nodes = Vec::new();
+        let mut stack = vec![(root, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            if nodes.iter().any(|n: &BranchNode| n.id == id) {
+                continue;
+            }
+            let Some(data) = sessions.iter().find(|s| s.id == id) else {
+                continue;
+            };
+            nodes.push(BranchNode {
+                id: id.clone(),
+                depth,
+                messages: data.messages.len(),
+                branch_point: data.branch_point,
+                title: data.title.clone(),
+            });
+            let mut children: Vec<&SessionData> = sessions
+                .iter()
+                .filter(|s| s.parent.as_deref() == Some(id.as_str()))
+                .collect();
+            children.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+            stack.extend(children.into_iter().map(|c| (c.id.clone(), depth + 1)));
+        }
+        Ok(nodes)
+    }
+
+    pub fn session_title(&self, session_id: &str) -> Option<String> {
+        self.read_session_data(session_id).ok()?.title
+    }
+
+    pub fn set_session_title(&self, session_id: &str, title: &str) -> Result<()> {
+        let mut data = self.read_session_data(session_id)?;
+        data.title = Some(title.to_string());
+        self.write_session_data(&data)
+    }
+
+    // Add `add` to and drop `remove` from the tags of a saved session
+    pub fn update_session_tags(
+        &self,
+        session_id: &str,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<Vec<String>> {
+        let mut data = self.read_session_data(session_id)?;
+        data.tags.retain(|t| !remove.contains(t));
+        for tag in add {
+            if !data.tags.contains(tag) {
+                data.tags.push(tag.clone());
+            }
+        }
+        self.write_session_data(&data)?;
+        Ok(data.tags)
+    }
+
+    // Write every saved session that passes `filter` as one line of a
+    // fine-tuning dataset; returns how many sessions were written and how
+    // many were skipped as unreadable
+    pub fn export_dataset(
+        &self,
+        format: DatasetFormat,
+        filter: &DatasetFilter,
+        redactors: &[Box<dyn Redactor>],
+        path: &Path,
+    ) -> Result<(usize, usize)> {
+        let mut ids = self.store.list()?;
+        ids.sort();
+        let mut out = String::new();
+        let mut count = 0;
+        let mut skipped = 0;
+        for id in ids {
+            let data = match self.read_session_data(&id) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!("Skipping unreadable session {}: {}", id, e);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            if !filter.matches(&data) {
+                continue;
+            }
+            out.push_str(&serde_json::to_string(&dataset::record(
+                &data, format, redactors,
+            ))?);
+            out.push('\n');
+            count += 1;
+        }
+        fs::write(path, out)?;
+        Ok((count, skipped))
+    }
+
+    // Write a session (saved or autosaved) to `path` in the given format
+    pub fn export_session(
+        &self,
+        session_id: &str,
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<()> {
+        let data = self.session_data(session_id)?;
+        fs::write(path, export::export(&data, format)?)?;
+        Ok(())
+    }
+
+    // Save the conversations of an OpenAI, Anthropic or ShareGPT export as new
+    // sessions; the format is detected unless given. Several conversations
+    // are saved as `<id>-1`, `<id>-2`, ...
+    pub fn import_session(
+        &self,
+        path: &Path,
+        session_id: &str,
+        format: Option<ImportFormat>,
+    ) -> Result<Vec<String>> {
+        let value: serde_json::Value = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| anyhow::anyhow!("{} is not valid JSON: {}", path.display(), e))?;
+        let format = match format {
+            Some(format) => format,
+            None => ImportFormat::detect(&value)?,
+        };
+        let conversations = import::import(&value, format)?;
+        let ids: Vec<String> = match conversations.len() {
+            0 => return Err(anyhow::anyhow!("No conversations in {}", path.display())),
+            1 => vec![session_id.to_string()],
+            n => (1..=n).map(|i| format!("{}-{}", session_id, i)).collect(),
+        };
+        for id in &ids {
+            validate_session_name(id)?;
+            if self.store.exists(id)? {
+                return Err(anyhow::anyhow!("Session '{}' already exists", id));
+            }
+        }
+        let now = chrono::Utc::now().to_rfc3339();
+        for (id, messages) in ids.iter().zip(conversations) {
+            self.write_session_data(&SessionData {
+                version: SCHEMA_VERSION,
+                id: id.clone(),
+                created_at: now.clone(),
+                updated_at: now.clone(),
+                title: None,
+                provider: String::new(),
+                model: value["model"].as_str().unwrap_or_default().to_string(),
+                cwd: String::new(),
+                message_count: messages.len(),
+                token_count: messages.iter().map(|m| m.metadata.token_count).sum(),
+                parent: None,
+                branch_point: None,
+                tags: Vec::new(),
+                messages,
+            })?;
+        }
+        Ok(ids)
+    }
+
+    // Session data for a saved session, or built from its transcript if it was never saved
+    pub fn session_data(&self, session_id: &str) -> Result<SessionData> {
+        let transcript = self.transcript_file(session_id)?;
+        if self.store.exists(session_id)? || !transcript.exists() {
+            return self.read_session_data(session_id);
+        }
+        self.transcript_data(session_id)
+    }
+
+    fn transcript_data(&self, session_id: &str) -> Result<SessionData> {
+        let messages = read_transcript(&self.transcript_file(session_id)?, self.cipher.as_ref())?;
+        Ok(SessionData {
+            version: SCHEMA_VERSION,
+            id: session_id.to_string(),
+            created_at: messages
+                .first()
+                .map(|m| m.metadata.timestamp.clone())
+                .unwrap_or_default(),
+            updated_at: messages
+                .last()
+                .map(|m| m.metadata.timestamp.clone())
+                .unwrap_or_default(),
+            title: None,
+            provider: String::new(),
+            model: String::new(),
+            cwd: String::new(),
+            message_count: messages.len(),
+            token_count: messages.iter().map(|m| estimate_tokens(&m.content)).sum(),
+            parent: None,
+            branch_point: None,
+            tags: Vec::new(),
+            messages,
+        })
+    }
+
+    fn transcript_file(&self, session_id: &str) -> Result<PathBuf> {
+        validate_session_name(session_id)?;
+        Ok(transcript_path(
+            &self.storage_dir.join(TRANSCRIPT_DIR),
+            session_id,
+        ))
+    }
+
+    fn read_session_data(&self, session_id: &str) -> Result<SessionData> {
+        self.store.read(session_id)
+    }
+
+    fn write_session_data(&self, data: &SessionData) -> Result<()> {
+        self.store.write(data)
+    }
+
+    // Turns matching every word of `query`: saved sessions first, then
+    // autosaved transcripts, each most recently active first
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let mut hits = self.store.search(query, limit)?;
+        let saved = self.store.list()?;
+        let mut sources = Vec::new();
+        let transcript_dir = self.storage_dir.join(TRANSCRIPT_DIR);
+        if transcript_dir.exists() {
+            for entry in fs::read_dir(&transcript_dir)? {
+                let path = entry?.path();
+                let Some(id) = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .and_then(|n| n.strip_suffix(".jsonl"))
+                    .map(String::from)
+                else {
+                    continue;
+                };
+                // A saved session hides the transcript with the same id
+                if !saved.contains(&id) {
+                    sources.push((id, path));
+                }
+            }
+        }
+        hits.extend(search_files(
+            self.storage_dir.join(TRANSCRIPT_INDEX_FILE),
+            self.cipher.clone(),
+            &sources,
+            |id| self.transcript_data(id),
+            query,
+            limit.saturating_sub(hits.len()),
+        )?);
+        Ok(hits)
+    }
+
     pub fn load_session(&self, session_id: &str, context: &mut ContextManager) -> Result<()> {
-        let path = self.storage_dir.join(format!("{}.json", session_id));
-        let file = fs::File::open(path)?;
-        let data: SessionData = serde_json::from_reader(file)?;
+        // Sessions that were never saved explicitly may still have a transcript
+        if !self.store.exists(session_id)? && self.transcript_file(session_id)?.exists() {
+            return self.recover_transcript(session_id, context);
+        }
+        let data = self.read_session_data(session_id)?;
+        // A resumed session keeps autosaving after its last save; the
+        // transcript then holds the newer conversation. It is empty when the
+        // session was resumed but nothing was said.
+        let transcript = self.transcript_file(session_id)?;
+        if transcript.exists() && is_newer(&modified_at(&transcript)?, &data.updated_at) {
+            let messages = read_transcript(&transcript, self.cipher.as_ref())?;
+            if !messages.is_empty() {
+                context.load_history(messages);
+                return Ok(());
+            }
+        }
 
         context.load_history(data.messages);
         Ok(())
     }
 
     pub fn list_sessions(&self) -> Result<Vec<String>> {
-        let mut sessions = Vec::new();
-        if self.storage_dir.exists() {
-            for entry in fs::read_dir(&self.storage_dir)? {
-                let entry = entry?;
-                if let Some(name) = entry
-                    .file_name()
-                    .to_str()
-                    .filter(|name| name.ends_with(".json"))
-                {
-                    sessions.push(name.trim_end_matches(".json").to_string());
+        self.store.list()
+    }
+
+    // Saved sessions and autosaved transcripts, most recently active first.
+    // A saved session hides the transcript with the same id.
+    pub fn list_recent_sessions(&self) -> Result<Vec<SessionEntry>> {
+        let mut entries = self.store.entries()?;
+        for transcript in self.list_transcripts()? {
+            match entries.iter_mut().find(|e| e.id == transcript.id) {
+                // A saved session that was resumed also has a transcript,
+                // which is the session's latest state when it is newer
+                Some(entry) => {
+                    entry.bytes += transcript.bytes;
+                    if is_newer(&transcript.updated_at, &entry.updated_at) {
+                        entry.updated_at = transcript.updated_at;
+                        entry.messages = transcript.messages;
+                        entry.tokens = transcript.tokens;
+                    }
                 }
+                None => entries.push(transcript),
             }
         }
-        Ok(sessions)
+        for entry in &mut entries {
+            entry.bytes += dir_size(&self.spill_dir(&entry.id));
+        }
+        entries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+        Ok(entries)
+    }
+}
+
+// Session names become file names, so they must not be able to point
+// anywhere else or collide with temporary and hidden files
+pub fn validate_session_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.chars().count() <= MAX_SESSION_NAME_CHARS
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!(
+            "Invalid session name '{}': use letters, digits, '-', '_' and '.', not starting with '.'",
+            name
+        ))
+    }
+}
+
+// Total size of the files directly in `dir`; 0 if it does not exist
+// Modification time of a file as an RFC 3339 timestamp
+fn modified_at(path: &Path) -> Result<String> {
+    let modified: chrono::DateTime<chrono::Utc> = fs::metadata(path)?.modified()?.into();
+    Ok(modified.to_rfc3339())
+}
+
+// Whether RFC 3339 timestamp `a` is later than `b`; an unparsable `b` is older than anything
+fn is_newer(a: &str, b: &str) -> bool {
+    match (
+        chrono::DateTime::parse_from_rfc3339(a),
+        chrono::DateTime::parse_from_rfc3339(b),
+    ) {
+        (Ok(a), Ok(b)) => a > b,
+        (Ok(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok()?.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .sum()
+        })
+        .unwrap_or(0)
+}
+
+// Replace a file in one step: write a temporary file next to it, sync it and
+// rename it over the target, so readers never see a half-written file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid path {}", path.display()))?
+        .to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()));
+    let result = (|| -> Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        std::io::Write::write_all(&mut file, contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+const MAX_TITLE_CHARS: usize = 60;
+
+// Ask the LLM for a short title describing the conversation
+pub async fn generate_title(llm: &dyn LLM, messages: &[Message]) -> Result<String> {
+    let excerpt = messages
+        .iter()
+        .filter(|m| matches!(m.role, Role::User | Role::Assistant))
+        .take(6)
+        .map(|m| {
+            let content: String = m.content.chars().take(500).collect();
+            format!("{:?}: {}", m.role, content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    if excerpt.is_empty() {
+        return Err(anyhow::anyhow!("No conversation to title"));
     }
+    let prompt = format!(
+        "Write a short title (at most 8 words) for the conversation below. Reply with the title only, no quotes or punctuation at the end.\n\n{}",
+        excerpt
+    );
+    let response = llm.complete(&[Message::new(Role::User, prompt)]).await?;
+    // Drop any thinking block and keep the first non-empty line
+    let text = match response.rfind("</thinking>") {
+        Some(i) => &response[i + "</thinking>".len()..],
+        None => response.as_str(),
+    };
+    let title = text
+        .lines()
+        .map(|l| {
+            l.trim()
+                .trim_matches(|c| c == '"' || c == '\'' || c == '#')
+                .trim()
+        })
+        .find(|l| !l.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Empty title"))?;
+    Ok(title.chars().take(MAX_TITLE_CHARS).collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::context::is_summary;
     use crate::llm::Role;
+    use storage::DirectoryStore;
     use tempfile::tempdir;
 
+    fn dir_manager(dir: &Path) -> SessionManager {
+        let store = Box::new(DirectoryStore::new(dir.to_path_buf(), 0));
+        SessionManager::new(dir.to_path_buf(), store)
+    }
+
     #[test]
     fn test_session_manager() {
         let dir = tempdir().unwrap();
-        let manager = SessionManager::new(dir.path().to_path_buf());
+        let manager = dir_manager(dir.path());
         let mut ctx = ContextManager::new(1000);
 
-        ctx.add_message(Message {
-            role: Role::User,
-            content: "Hello".to_string(),
-        });
+        ctx.add_message(Message::new(Role::User, "Hello".to_string()));
 
         manager.save_session("test_session", &ctx).unwrap();
 
@@ -91,4 +894,563 @@ mod tests {
         let sessions = manager.list_sessions().unwrap();
         assert!(sessions.contains(&"test_session".to_string()));
     }
+
+    struct TitleLLM;
+
+    #[async_trait::async_trait]
+    impl LLM for TitleLLM {
+        async fn complete(&self, _messages: &[Message]) -> Result<String> {
+            Ok("<thinking>short</thinking>\n\"Greeting\"\n".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_generated_title() {
+        let dir = tempdir().unwrap();
+        let manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        assert!(generate_title(&TitleLLM, ctx.get_history()).await.is_err());
+        ctx.add_message(Message::new(Role::User, "Hello"));
+        manager.save_session("titled", &ctx).unwrap();
+        assert!(manager.session_title("titled").is_none());
+
+        let title = generate_title(&TitleLLM, ctx.get_history()).await.unwrap();
+        assert_eq!(title, "Greeting");
+        manager.set_session_title("titled", &title).unwrap();
+
+        // Re-saving keeps created_at and title but refreshes updated_at
+        let first = manager.read_session_data("titled").unwrap();
+        assert_eq!(first.message_count, 1);
+        manager.save_session("titled", &ctx).unwrap();
+        let second = manager.read_session_data("titled").unwrap();
+        assert_eq!(second.created_at, first.created_at);
+        assert_eq!(second.title.as_deref(), Some("Greeting"));
+        assert!(second.updated_at >= first.updated_at);
+        assert_eq!(
+            manager.list_recent_sessions().unwrap()[0].title.as_deref(),
+            Some("Greeting")
+        );
+    }
+
+    #[test]
+    fn test_pinned_messages_survive_save() {
+        let dir = tempdir().unwrap();
+        let manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "Hello"));
+        ctx.add_message(Message::new(Role::Assistant, "Hi"));
+        ctx.pin(0).unwrap();
+        manager.save_session("pinned", &ctx).unwrap();
+
+        let mut loaded = ContextManager::new(1000);
+        manager.load_session("pinned", &mut loaded).unwrap();
+        let pinned = loaded.pinned_messages();
+        assert_eq!(pinned.len(), 1);
+        assert_eq!((pinned[0].0, pinned[0].1.content.as_str()), (0, "Hello"));
+    }
+
+    #[test]
+    fn test_autosave_transcript() {
+        let dir = tempdir().unwrap();
+        let mut manager = dir_manager(dir.path());
+        let id = manager.start_autosave(None).unwrap();
+        let mut ctx = ContextManager::new(1000);
+
+        ctx.add_message(Message::new(Role::User, "Hello"));
+        manager.autosave(&ctx).unwrap();
+        ctx.add_message(Message::new(Role::Assistant, "Hi"));
+        manager.autosave(&ctx).unwrap();
+        manager.autosave(&ctx).unwrap();
+
+        // A crash mid-write leaves a torn line that recovery skips
+        let path = transcript_path(&dir.path().join(TRANSCRIPT_DIR), &id);
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, b"{\"role\":\"us").unwrap();
+
+        let other = dir_manager(dir.path());
+        let transcripts = other.list_transcripts().unwrap();
+        assert_eq!(transcripts.len(), 1);
+        assert_eq!(transcripts[0].id, id);
+        assert_eq!(transcripts[0].messages, 2);
+
+        let mut recovered = ContextManager::new(1000);
+        other.load_session(&id, &mut recovered).unwrap();
+        assert_eq!(recovered.get_history().len(), 2);
+        assert_eq!(recovered.get_history()[1].content, "Hi");
+
+        // Resuming continues the same transcript without writing the history again
+        let mut resumed = dir_manager(dir.path());
+        assert_eq!(resumed.start_autosave(Some(&id)).unwrap(), id);
+        resumed.autosave(&recovered).unwrap();
+        recovered.add_message(Message::new(Role::User, "Again"));
+        resumed.autosave(&recovered).unwrap();
+        assert_eq!(read_transcript(&path, None).unwrap().len(), 3);
+        assert_eq!(other.list_recent_sessions().unwrap().len(), 1);
+
+        // Saving copies the outputs spilled so far to the saved session
+        fs::create_dir_all(resumed.spill_dir(&id)).unwrap();
+        fs::write(resumed.spill_dir(&id).join("bash-1.txt"), "out").unwrap();
+        resumed.save_session("named", &recovered).unwrap();
+        assert!(resumed.spill_dir("named").join("bash-1.txt").exists());
+
+        // A rewind hides the discarded messages from readers of the transcript
+        recovered.truncate(1);
+        resumed.record_rewind(&recovered).unwrap();
+        recovered.add_message(Message::new(Role::User, "Instead"));
+        resumed.autosave(&recovered).unwrap();
+        let contents: Vec<String> = read_transcript(&path, None)
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents, ["Hello", "Instead"]);
+        recovered.clear_history();
+        resumed.record_rewind(&recovered).unwrap();
+        assert!(read_transcript(&path, None).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_compression_in_transcript() {
+        let dir = tempdir().unwrap();
+        let mut manager = dir_manager(dir.path());
+        let id = manager.start_autosave(None).unwrap();
+        let mut ctx = ContextManager::new(10);
+        ctx.add_message(Message::new(Role::System, "You are a bot"));
+        for i in 0..10 {
+            ctx.add_message(Message::new(Role::User, format!("Message {}", i)));
+        }
+        manager.autosave(&ctx).unwrap();
+        ctx.compress().await.unwrap();
+        manager.record_compression(&ctx).unwrap();
+        ctx.add_message(Message::new(Role::User, "After"));
+        manager.autosave(&ctx).unwrap();
+
+        // Recovery gives the compressed conversation the agent was using
+        let mut recovered = ContextManager::new(10);
+        dir_manager(dir.path())
+            .recover_transcript(&id, &mut recovered)
+            .unwrap();
+        let contents: Vec<&str> = recovered
+            .get_history()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        let expected: Vec<&str> = ctx
+            .get_history()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, expected);
+        assert_eq!(contents.len(), 7);
+        assert!(is_summary(&recovered.get_history()[1]));
+
+        // Resuming does not write the replaced messages again
+        let mut resumed = dir_manager(dir.path());
+        resumed.start_autosave(Some(&id)).unwrap();
+        resumed.autosave(&recovered).unwrap();
+        let path = transcript_path(&dir.path().join(TRANSCRIPT_DIR), &id);
+        assert_eq!(read_transcript(&path, None).unwrap().len(), 7);
+    }
+
+    #[test]
+    fn test_resume_saved_session_after_autosave() {
+        let dir = tempdir().unwrap();
+        let manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "Hello"));
+        manager.save_session("saved", &ctx).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        manager.save_session("other", &ctx).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        // Resume, keep talking, then go away without saving
+        let mut resumed = dir_manager(dir.path());
+        let mut live = ContextManager::new(1000);
+        resumed.load_session("saved", &mut live).unwrap();
+        resumed.start_autosave(Some("saved")).unwrap();
+        live.add_message(Message::new(Role::Assistant, "Hi"));
+        resumed.autosave(&live).unwrap();
+        drop(resumed);
+
+        // The newer transcript wins over the saved file, in listings too
+        let entries = manager.list_recent_sessions().unwrap();
+        assert_eq!((entries[0].id.as_str(), entries[0].messages), ("saved", 2));
+        let mut again = dir_manager(dir.path());
+        let mut ctx2 = ContextManager::new(1000);
+        again.load_session("saved", &mut ctx2).unwrap();
+        let contents: Vec<&str> = ctx2
+            .get_history()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, ["Hello", "Hi"]);
+
+        // Resuming again continues the transcript without losing or repeating messages
+        again.start_autosave(Some("saved")).unwrap();
+        ctx2.add_message(Message::new(Role::User, "Again"));
+        again.autosave(&ctx2).unwrap();
+        let path = transcript_path(&dir.path().join(TRANSCRIPT_DIR), "saved");
+        assert_eq!(read_transcript(&path, None).unwrap().len(), 3);
+
+        // Saving makes the saved file current again
+        again.save_session("saved", &ctx2).unwrap();
+        let mut ctx3 = ContextManager::new(1000);
+        manager.load_session("saved", &mut ctx3).unwrap();
+        assert_eq!(ctx3.get_history().len(), 3);
+    }
+
+    #[test]
+    fn test_list_recent_sessions() {
+        let dir = tempdir().unwrap();
+        let mut manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "Hello"));
+        manager.save_session("older", &ctx).unwrap();
+        let id = manager.start_autosave(None).unwrap();
+        manager.autosave(&ctx).unwrap();
+
+        // The current transcript is not offered for resuming
+        assert_eq!(manager.list_recent_sessions().unwrap().len(), 1);
+
+        let other = dir_manager(dir.path());
+        let entries = other.list_recent_sessions().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().any(|e| e.id == id && e.autosaved));
+        assert!(entries.iter().any(|e| e.id == "older" && !e.autosaved));
+    }
+
+    #[test]
+    fn test_fork_and_list_branches() {
+        let dir = tempdir().unwrap();
+        let manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "Plan A or B?"));
+        ctx.add_message(Message::new(Role::Assistant, "A"));
+        manager.save_session("main", &ctx).unwrap();
+
+        ctx.truncate(1);
+        ctx.add_message(Message::new(Role::Assistant, "B"));
+        let branch = manager.next_branch_id("main");
+        assert_eq!(branch, "main-1");
+        manager.fork_session(&branch, Some("main"), &ctx).unwrap();
+        assert!(manager.fork_session(&branch, Some("main"), &ctx).is_err());
+
+        let nodes = manager.list_branches("main-1").unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!((nodes[0].id.as_str(), nodes[0].depth), ("main", 0));
+        assert_eq!((nodes[1].id.as_str(), nodes[1].depth), ("main-1", 1));
+        assert_eq!(nodes[1].branch_point, Some(1));
+    }
+
+    #[test]
+    fn test_search_sessions() {
+        let dir = tempdir().unwrap();
+        let manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(
+            Role::User,
+            "How do I configure the Tokio runtime?",
+        ));
+        ctx.add_message(Message::new(
+            Role::Assistant,
+            "Use #[tokio::main] with flavor",
+        ));
+        manager.save_session("rust", &ctx).unwrap();
+
+        let mut other = ContextManager::new(1000);
+        other.add_message(Message::new(Role::User, "Write a tokio-free parser"));
+        manager.save_session("parser", &other).unwrap();
+
+        // The index is written on save and not listed as a session
+        assert!(dir.path().join("index").join("search.json").exists());
+        assert_eq!(manager.list_sessions().unwrap().len(), 2);
+
+        let hits = manager.search("tokio runtime", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].session_id.as_str(), hits[0].turn), ("rust", 0));
+        assert!(hits[0].snippet.contains("Tokio runtime"));
+
+        // Prefix match on the last word, across sessions
+        assert_eq!(manager.search("tok", 10).unwrap().len(), 3);
+        assert_eq!(manager.search("tok", 2).unwrap().len(), 2);
+
+        // Autosaved transcripts are indexed lazily at search time
+        let mut autosaving = dir_manager(dir.path());
+        let id = autosaving.start_autosave(None).unwrap();
+        let mut live = ContextManager::new(1000);
+        live.add_message(Message::new(Role::User, "grep for flamegraph"));
+        autosaving.autosave(&live).unwrap();
+        let hits = manager.search("flamegraph", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].session_id, id);
+
+        // An unreadable session is skipped instead of failing the search
+        fs::write(dir.path().join("rust.json"), "{").unwrap();
+        let hits = manager.search("tok", 10).unwrap();
+        assert!(hits.iter().all(|h| h.session_id != "rust"));
+        assert!(hits.iter().any(|h| h.session_id == "parser"));
+
+        fs::remove_file(dir.path().join("parser.json")).unwrap();
+        assert_eq!(manager.search("parser", 10).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_search_matches_the_same_in_every_store() {
+        let dir = tempdir().unwrap();
+        let sqlite = storage::open_store("sqlite", &dir.path().join("sql"), 0, None).unwrap();
+        let managers = [
+            dir_manager(&dir.path().join("dir")),
+            SessionManager::new(dir.path().join("sql"), sqlite),
+        ];
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "Profile the Tokio runtime"));
+        ctx.add_message(Message::new(Role::Assistant, "Überprüfe die Laufzeit"));
+        for manager in &managers {
+            manager.save_session("s", &ctx).unwrap();
+            let turns = |query: &str| -> Vec<usize> {
+                manager
+                    .search(query, 10)
+                    .unwrap()
+                    .iter()
+                    .map(|h| h.turn)
+                    .collect()
+            };
+            assert_eq!(turns("tokio runt"), [0]);
+            // Only the last word may be partial
+            assert!(turns("untime").is_empty());
+            assert!(turns("tok runtime").is_empty());
+            // Case folding is not limited to ASCII
+            assert_eq!(turns("überprüfe"), [1]);
+        }
+    }
+
+    #[test]
+    fn test_session_names_and_backups() {
+        let dir = tempdir().unwrap();
+        let storage = dir.path().join("sessions");
+        let manager = SessionManager::new(
+            storage.clone(),
+            Box::new(DirectoryStore::new(storage.clone(), 2)),
+        );
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "v1"));
+
+        for name in ["../escape", "a/b", "", ".hidden", "x\\y"] {
+            assert!(manager.save_session(name, &ctx).is_err(), "{}", name);
+        }
+        assert!(!dir.path().join("escape.json").exists());
+        let mut loaded = ContextManager::new(1000);
+        assert!(manager.load_session("../sessions/x", &mut loaded).is_err());
+
+        manager.save_session("notes_v1.2", &ctx).unwrap();
+        for version in ["v2", "v3", "v4"] {
+            ctx.add_message(Message::new(Role::User, version));
+            manager.save_session("notes_v1.2", &ctx).unwrap();
+        }
+        // Only the rename target is left behind, no temporary files
+        let files: Vec<String> = fs::read_dir(&storage)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|n| n.ends_with(".tmp"))
+            .collect();
+        assert!(files.is_empty(), "{:?}", files);
+
+        let backup = |n: usize| {
+            let path = storage
+                .join("backups")
+                .join(format!("notes_v1.2.json.{}", n));
+            let data: SessionData = serde_json::from_reader(fs::File::open(path).unwrap()).unwrap();
+            data.messages.len()
+        };
+        assert_eq!(backup(1), 3);
+        assert_eq!(backup(2), 2);
+        assert!(!storage.join("backups").join("notes_v1.2.json.3").exists());
+        assert_eq!(
+            manager
+                .read_session_data("notes_v1.2")
+                .unwrap()
+                .messages
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_delete_rename_and_prune() {
+        let dir = tempdir().unwrap();
+        let mut manager = dir_manager(dir.path());
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "Hello"));
+        manager.save_session("first", &ctx).unwrap();
+        manager.fork_session("child", Some("first"), &ctx).unwrap();
+
+        // The transcript and spilled outputs of a saved session move with it
+        let mut resumed = dir_manager(dir.path());
+        resumed.start_autosave(Some("first")).unwrap();
+        resumed.autosave(&ctx).unwrap();
+        fs::create_dir_all(resumed.spill_dir("first")).unwrap();
+        resumed.rename_session("first", "renamed").unwrap();
+        assert_eq!(resumed.current_session_id(), Some("renamed"));
+        assert!(resumed.spill_dir("renamed").exists());
+        assert!(!resumed.spill_dir("first").exists());
+        drop(resumed);
+        assert!(
+            manager
+                .list_transcripts()
+                .unwrap()
+                .iter()
+                .all(|t| t.id == "renamed")
+        );
+        assert!(manager.rename_session("child", "renamed").is_err());
+        assert!(manager.rename_session("renamed", "../out").is_err());
+        let mut sessions = manager.list_sessions().unwrap();
+        sessions.sort();
+        assert_eq!(sessions, ["child", "renamed"]);
+        assert_eq!(
+            manager.store.read("child").unwrap().parent.as_deref(),
+            Some("renamed")
+        );
+
+        // Deleting an autosaved session also drops its spilled outputs
+        let mut autosaving = dir_manager(dir.path());
+        let id = autosaving.start_autosave(None).unwrap();
+        autosaving.autosave(&ctx).unwrap();
+        fs::create_dir_all(autosaving.spill_dir(&id)).unwrap();
+        assert!(autosaving.delete_session(&id).is_err());
+        manager.delete_session(&id).unwrap();
+        assert!(!manager.spill_dir(&id).exists());
+        assert!(
+            manager
+                .list_transcripts()
+                .unwrap()
+                .iter()
+                .all(|t| t.id != id)
+        );
+        assert!(manager.delete_session(&id).is_err());
+
+        let policy = RetentionPolicy {
+            max_count: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(manager.prune(&policy, true, None).unwrap().len(), 1);
+        assert_eq!(manager.list_sessions().unwrap().len(), 2);
+        manager.prune(&policy, false, None).unwrap();
+        assert_eq!(manager.list_sessions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_prune_by_size() {
+        let dir = tempdir().unwrap();
+        let manager = SessionManager::new(
+            dir.path().to_path_buf(),
+            Box::new(DirectoryStore::new(dir.path().to_path_buf(), 2)),
+        );
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "v1"));
+        manager.save_session("old", &ctx).unwrap();
+        for version in ["v2", "v3"] {
+            ctx.add_message(Message::new(Role::User, version));
+            manager.save_session("old", &ctx).unwrap();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        manager.save_session("new", &ctx).unwrap();
+
+        // Retention sizes count backups and spilled outputs too
+        fs::create_dir_all(manager.spill_dir("old")).unwrap();
+        fs::write(manager.spill_dir("old").join("bash-1.txt"), [b'x'; 1000]).unwrap();
+        let on_disk: u64 = [
+            dir.path().join("old.json"),
+            dir.path().join("backups").join("old.json.1"),
+            dir.path().join("backups").join("old.json.2"),
+            manager.spill_dir("old").join("bash-1.txt"),
+        ]
+        .iter()
+        .map(|p| fs::metadata(p).unwrap().len())
+        .sum();
+        let entries = manager.list_recent_sessions().unwrap();
+        assert_eq!(entries[1].id, "old");
+        assert_eq!(entries[1].bytes, on_disk);
+
+        let policy = RetentionPolicy {
+            max_size_bytes: Some(entries[0].bytes + 1000),
+            ..Default::default()
+        };
+        assert_eq!(manager.prune(&policy, false, None).unwrap(), ["old"]);
+        assert_eq!(manager.list_sessions().unwrap(), ["new"]);
+        assert!(!manager.spill_dir("old").exists());
+
+        // A session that cannot be deleted does not stop the rest of the
+        // cleanup, and the current and kept sessions are left alone
+        for id in ["oldest", "older", "newer", "newest"] {
+            manager.save_session(id, &ctx).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        fs::create_dir_all(dir.path().join(TRANSCRIPT_DIR).join("older.jsonl")).unwrap();
+        let mut in_use = dir_manager(dir.path());
+        in_use.start_autosave(Some("newer")).unwrap();
+        let policy = RetentionPolicy {
+            max_count: Some(1),
+            ..Default::default()
+        };
+        let mut pruned = in_use.prune(&policy, false, Some("new")).unwrap();
+        pruned.sort();
+        assert_eq!(pruned, ["oldest"]);
+        let mut sessions = manager.list_sessions().unwrap();
+        sessions.sort();
+        assert_eq!(sessions, ["new", "newer", "newest"]);
+    }
+
+    #[test]
+    fn test_encrypted_sessions() {
+        let dir = tempdir().unwrap();
+        let encrypted_manager = |key: &str| {
+            let cipher = Some(SessionCipher::from_key_str(key).unwrap());
+            let store = storage::open_store("dir", dir.path(), 0, cipher.clone()).unwrap();
+            let mut manager = SessionManager::new(dir.path().to_path_buf(), store);
+            manager.set_cipher(cipher);
+            manager
+        };
+        let key = "ab".repeat(32);
+        let mut manager = encrypted_manager(&key);
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::User, "my password is hunter2"));
+        // Sessions saved before encryption was enabled still load
+        dir_manager(dir.path()).save_session("plain", &ctx).unwrap();
+        manager.save_session("secret", &ctx).unwrap();
+        let id = manager.start_autosave(None).unwrap();
+        manager.autosave(&ctx).unwrap();
+
+        let on_disk = [
+            dir.path().join("secret.json"),
+            dir.path().join("index").join("search.json"),
+            transcript_path(&dir.path().join(TRANSCRIPT_DIR), &id),
+        ];
+        for path in &on_disk {
+            assert!(!fs::read_to_string(path).unwrap().contains("hunter2"));
+        }
+
+        let reader = encrypted_manager(&key);
+        for session in ["plain", "secret", id.as_str()] {
+            let mut loaded = ContextManager::new(1000);
+            reader.load_session(session, &mut loaded).unwrap();
+            assert_eq!(loaded.get_history()[0].content, "my password is hunter2");
+        }
+        assert_eq!(reader.search("hunter2", 10).unwrap().len(), 3);
+
+        // Sessions and transcripts a key cannot read are left out of listings
+        let entries = encrypted_manager(&"cd".repeat(32))
+            .list_recent_sessions()
+            .unwrap();
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["plain"]);
+        assert!(
+            storage::open_store(
+                "sqlite",
+                dir.path(),
+                0,
+                Some(SessionCipher::from_key_str(&key).unwrap())
+            )
+            .is_err()
+        );
+    }
 }
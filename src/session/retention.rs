@@ -0,0 +1,122 @@
+use super::SessionEntry;
+
+// Limits on what the session directory keeps. Sessions beyond any limit are
+// deleted oldest first; the current session is never a candidate.
+#[derive(Debug, Clone, Default)]
+pub struct RetentionPolicy {
+    pub max_age_days: Option<u64>,
+    pub max_count: Option<usize>,
+    pub max_size_bytes: Option<u64>,
+}
+
+impl RetentionPolicy {
+    pub fn is_empty(&self) -> bool {
+        self.max_age_days.is_none() && self.max_count.is_none() && self.max_size_bytes.is_none()
+    }
+
+    // Ids of the sessions to delete from `entries`, which must be sorted most
+    // recently active first
+    pub fn expired(
+        &self,
+        entries: &[SessionEntry],
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Vec<String> {
+        // An age too large to represent is no limit at all
+        let cutoff = self
+            .max_age_days
+            .and_then(|days| i64::try_from(days).ok())
+            .and_then(chrono::Duration::try_days)
+            .and_then(|age| now.checked_sub_signed(age));
+        let mut kept = 0;
+        let mut size = 0;
+        // Once the size budget is used up, everything older goes too
+        let mut full = false;
+        let mut expired = Vec::new();
+        for entry in entries {
+            let too_old = match (
+                cutoff,
+                chrono::DateTime::parse_from_rfc3339(&entry.updated_at),
+            ) {
+                (Some(cutoff), Ok(updated)) => updated < cutoff,
+                _ => false,
+            };
+            let too_many = self.max_count.is_some_and(|max| kept >= max);
+            full = full
+                || self
+                    .max_size_bytes
+                    .is_some_and(|max| size + entry.bytes > max);
+            if too_old || too_many || full {
+                expired.push(entry.id.clone());
+            } else {
+                kept += 1;
+                size += entry.bytes;
+            }
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        id: &str,
+        days_ago: i64,
+        bytes: u64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> SessionEntry {
+        SessionEntry {
+            id: id.to_string(),
+            title: None,
+            provider: String::new(),
+            model: String::new(),
+            cwd: String::new(),
+            updated_at: (now - chrono::Duration::days(days_ago)).to_rfc3339(),
+            messages: 1,
+            tokens: 1,
+            bytes,
+            autosaved: false,
+        }
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let now = chrono::Utc::now();
+        let entries = vec![
+            entry("a", 0, 100, now),
+            entry("b", 2, 300, now),
+            entry("c", 5, 100, now),
+            entry("d", 40, 10, now),
+        ];
+        let policy = |age, count, size| RetentionPolicy {
+            max_age_days: age,
+            max_count: count,
+            max_size_bytes: size,
+        };
+        assert!(policy(None, None, None).expired(&entries, now).is_empty());
+        assert_eq!(policy(Some(30), None, None).expired(&entries, now), ["d"]);
+        assert_eq!(
+            policy(None, Some(2), None).expired(&entries, now),
+            ["c", "d"]
+        );
+        assert_eq!(
+            policy(None, None, Some(250)).expired(&entries, now),
+            ["b", "c", "d"]
+        );
+        assert_eq!(
+            policy(Some(30), Some(3), Some(450)).expired(&entries, now),
+            ["c", "d"]
+        );
+        assert!(
+            policy(Some(u64::MAX), None, None)
+                .expired(&entries, now)
+                .is_empty()
+        );
+        assert!(
+            policy(Some(i64::MAX as u64 / 86_400 / 1000), None, None)
+                .expired(&entries, now)
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use super::crypto::{self, SessionCipher};
+use super::{SessionData, write_atomic};
+use crate::llm::Role;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+// A matching turn of a session
+pub struct SearchHit {
+    pub session_id: String,
+    pub title: Option<String>,
+    // Index of the matching message in the session
+    pub turn: usize,
+    pub role: Role,
+    pub snippet: String,
+}
+
+// Inverted index from terms to the session turns containing them, persisted
+// as one JSON file. Each session records the modification time of the file
+// it was indexed from, so stale entries can be found without reading them,
+// and the text of its turns, so hits are shown without reading the session.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SearchIndex {
+    #[serde(skip)]
+    path: PathBuf,
+    // The index holds message text, so it is encrypted like the sessions
+    #[serde(skip)]
+    cipher: Option<SessionCipher>,
+    sessions: BTreeMap<String, IndexedSession>,
+    // term -> session id -> message indices
+    postings: BTreeMap<String, BTreeMap<String, Vec<usize>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedSession {
+    // Source file modification time (ms since epoch)
+    stamp: u64,
+    title: Option<String>,
+    // message index -> role and content of the non-system turns
+    turns: BTreeMap<usize, (Role, String)>,
+}
+
+impl SearchIndex {
+    // Load the index at `path`; a missing or unreadable index starts empty and is rebuilt
+    pub fn open(path: PathBuf, cipher: Option<SessionCipher>) -> Self {
+        let mut index = fs::read(&path)
+            .ok()
+            .and_then(|data| crypto::decode(cipher.as_ref(), data, "search index").ok())
+            .and_then(|data| serde_json::from_slice::<SearchIndex>(&data).ok())
+            .unwrap_or_default();
+        index.path = path;
+        index.cipher = cipher;
+        index
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let data = crypto::encode(self.cipher.as_ref(), serde_json::to_vec(self)?)?;
+        write_atomic(&self.path, &data)
+    }
+
+    // Whether `session_id` was indexed from a file with this modification time
+    pub fn is_current(&self, session_id: &str, stamp: u64) -> bool {
+        self.sessions.get(session_id).map(|s| s.stamp) == Some(stamp)
+    }
+
+    pub fn indexed_sessions(&self) -> Vec<String> {
+        self.sessions.keys().cloned().collect()
+    }
+
+    pub fn update(&mut self, data: &SessionData, stamp: u64) {
+        self.remove(&data.id);
+        let mut indexed = BTreeMap::new();
+        for (turn, message) in data.messages.iter().enumerate() {
+            if message.role == Role::System {
+                continue;
+            }
+            indexed.insert(turn, (message.role.clone(), message.content.clone()));
+            for term in tokenize(&message.content)
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+            {
+                let turns = self
+                    .postings
+                    .entry(term)
+                    .or_default()
+                    .entry(data.id.clone())
+                    .or_default();
+                turns.push(turn);
+            }
+        }
+        self.sessions.insert(
+            data.id.clone(),
+            IndexedSession {
+                stamp,
+                title: data.title.clone(),
+                turns: indexed,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, session_id: &str) {
+        if self.sessions.remove(session_id).is_none() {
+            return;
+        }
+        self.postings.retain(|_, sessions| {
+            sessions.remove(session_id);
+            !sessions.is_empty()
+        });
+    }
+
+    // Turns matching the query as in `matches`, grouped by session
+    pub fn lookup(&self, query: &str) -> BTreeMap<String, Vec<usize>> {
+        let terms = tokenize(query);
+        let mut result: Option<BTreeMap<String, BTreeSet<usize>>> = None;
+        for (i, term) in terms.iter().enumerate() {
+            let mut matches: BTreeMap<String, BTreeSet<usize>> = BTreeMap::new();
+            let postings: Vec<&BTreeMap<String, Vec<usize>>> = if i + 1 == terms.len() {
+                self.postings
+                    .range(term.clone()..)
+                    .take_while(|(t, _)| t.starts_with(term.as_str()))
+                    .map(|(_, p)| p)
+                    .collect()
+            } else {
+                self.postings.get(term).into_iter().collect()
+            };
+            for sessions in postings {
+                for (id, turns) in sessions {
+                    matches.entry(id.clone()).or_default().extend(turns);
+                }
+            }
+            result = Some(match result {
+                None => matches,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(id, turns)| {
+                        let both: BTreeSet<usize> = matches
+                            .get(&id)
+                            .map(|m| turns.intersection(m).copied().collect())
+                            .unwrap_or_default();
+                        (!both.is_empty()).then_some((id, both))
+                    })
+                    .collect(),
+            });
+        }
+        result
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(id, turns)| (id, turns.into_iter().collect()))
+            .collect()
+    }
+}
+
+// Search sessions stored as files through the index at `index_path`. Entries
+// whose file changed since they were indexed are reloaded with `load` first.
+// Hits come from the most recently modified files first.
+pub fn search_files(
+    index_path: PathBuf,
+    cipher: Option<SessionCipher>,
+    sources: &[(String, PathBuf)],
+    load: impl Fn(&str) -> Result<SessionData>,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SearchHit>> {
+    let mut index = SearchIndex::open(index_path, cipher);
+    let mut changed = false;
+    let mut stamps = Vec::new();
+    for (id, path) in sources {
+        let stamp = file_stamp(path)?;
+        if !index.is_current(id, stamp) {
+            match load(id) {
+                Ok(data) => index.update(&data, stamp),
+                Err(e) => {
+                    log::warn!("Skipping unreadable session {}: {}", id, e);
+                    index.remove(id);
+                }
+            }
+            changed = true;
+        }
+        stamps.push((id.clone(), stamp));
+    }
+    for id in index.indexed_sessions() {
+        if !sources.iter().any(|(s, _)| *s == id) {
+            index.remove(&id);
+            changed = true;
+        }
+    }
+    if changed && let Err(e) = index.save() {
+        log::warn!("Failed to save search index: {}", e);
+    }
+
+    let matches = index.lookup(query);
+    stamps.sort_by_key(|(_, stamp)| std::cmp::Reverse(*stamp));
+    let mut hits = Vec::new();
+    for (id, _) in stamps {
+        let Some(turns) = matches.get(&id) else {
+            continue;
+        };
+        let Some(session) = index.sessions.get(&id) else {
+            continue;
+        };
+        for turn in turns {
+            let Some((role, content)) = session.turns.get(turn) else {
+                continue;
+            };
+            if hits.len() >= limit {
+                return Ok(hits);
+            }
+            hits.push(SearchHit {
+                session_id: id.clone(),
+                title: session.title.clone(),
+                turn: *turn,
+                role: role.clone(),
+                snippet: snippet(content, query),
+            });
+        }
+    }
+    Ok(hits)
+}
+
+// Lowercased words of letters, digits and underscores
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+// Whether `content` contains every query term as a whole word. The last term
+// also matches as a prefix, so partially typed words still find results.
+// Both session stores match with this, through the index or directly.
+pub fn matches(content: &str, terms: &[String]) -> bool {
+    let words: BTreeSet<String> = tokenize(content).into_iter().collect();
+    !terms.is_empty()
+        && terms.iter().enumerate().all(|(i, term)| {
+            if i + 1 == terms.len() {
+                words
+                    .range(term.clone()..)
+                    .next()
+                    .is_some_and(|w| w.starts_with(term.as_str()))
+            } else {
+                words.contains(term)
+            }
+        })
+}
+
+// Modification time of a file in ms, used to detect stale index entries
+pub fn file_stamp(path: &Path) -> Result<u64> {
+    let modified = fs::metadata(path)?.modified()?;
+    Ok(modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default())
+}
+
+// A one-line excerpt around the first occurrence of a query term
+pub fn snippet(content: &str, query: &str) -> String {
+    let flat = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    let lower = flat.to_lowercase();
+    // Lowercasing can change byte lengths, so fall back to the start on mismatch
+    let start = tokenize(query)
+        .iter()
+        .filter_map(|t| lower.find(t.as_str()))
+        .min()
+        .filter(|_| lower.len() == flat.len())
+        .unwrap_or(0);
+    let chars_before = flat[..start].chars().count();
+    let from = chars_before.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let excerpt: String = flat
+        .chars()
+        .skip(from)
+        .take(SNIPPET_CONTEXT_CHARS * 2 + query.chars().count())
+        .collect();
+    let mut out = String::new();
+    if from > 0 {
+        out.push_str("...");
+    }
+    out.push_str(&excerpt);
+    if from + excerpt.chars().count() < flat.chars().count() {
+        out.push_str("...");
+    }
+    out
+}
@@ -0,0 +1,439 @@
+use super::migrate::SCHEMA_VERSION;
+use super::search::{SearchHit, matches, snippet, tokenize};
+use super::storage::SessionStore;
+use super::{SessionData, SessionEntry, validate_session_name};
+use crate::llm::{Message, MessageMetadata, Role};
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use std::fs;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    title TEXT,
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    cwd TEXT NOT NULL,
+    message_count INTEGER NOT NULL,
+    token_count INTEGER NOT NULL,
+    parent TEXT,
+    branch_point INTEGER
+);
+CREATE TABLE IF NOT EXISTS messages (
+    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    seq INTEGER NOT NULL,
+    id TEXT NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    tool_name TEXT,
+    token_count INTEGER NOT NULL,
+    pinned INTEGER NOT NULL,
+    final_answer INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (session_id, seq)
+);
+CREATE TABLE IF NOT EXISTS tags (
+    session_id TEXT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (session_id, tag)
+);
+CREATE INDEX IF NOT EXISTS sessions_updated_at ON sessions(updated_at);
+";
+
+// Sessions in an embedded SQLite database: one row per session and one per
+// message, so listings and searches are queries instead of file parsing
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
+        // The database schema follows the session schema version it stores
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version as u64 > SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "Session database {} has schema version {}, but this build only supports up to {}",
+                path.display(),
+                version,
+                SCHEMA_VERSION
+            ));
+        }
+        conn.execute_batch(SCHEMA)?;
+        // Databases created before final answers were flagged lack the column
+        let has_final: bool = conn
+            .prepare("SELECT 1 FROM pragma_table_info('messages') WHERE name = 'final_answer'")?
+            .exists([])?;
+        if !has_final {
+            conn.execute_batch(
+                "ALTER TABLE messages ADD COLUMN final_answer INTEGER NOT NULL DEFAULT 0",
+            )?;
+        }
+        conn.execute_batch(&format!("PRAGMA user_version = {}", SCHEMA_VERSION))?;
+        Ok(Self { conn })
+    }
+
+    fn read_messages(&self, session_id: &str) -> Result<Vec<Message>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT role, content, id, timestamp, tool_name, token_count, pinned, final_answer
+             FROM messages WHERE session_id = ?1 ORDER BY seq",
+        )?;
+        let rows = stmt.query_map([session_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                Message {
+                    role: Role::User,
+                    content: row.get(1)?,
+                    metadata: MessageMetadata {
+                        id: row.get(2)?,
+                        timestamp: row.get(3)?,
+                        tool_name: row.get(4)?,
+                        token_count: count(row, 5)?,
+                        pinned: row.get(6)?,
+                        final_answer: row.get(7)?,
+                    },
+                },
+            ))
+        })?;
+        let mut messages = Vec::new();
+        for row in rows {
+            let (role, mut message) = row?;
+            message.role = parse_role(&role)?;
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    fn read_tags(&self, session_id: &str) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag FROM tags WHERE session_id = ?1 ORDER BY rowid")?;
+        let tags = stmt
+            .query_map([session_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(tags)
+    }
+}
+
+impl SessionStore for SqliteStore {
+    fn exists(&self, session_id: &str) -> Result<bool> {
+        Ok(self
+            .conn
+            .query_row("SELECT 1 FROM sessions WHERE id = ?1", [session_id], |_| {
+                Ok(())
+            })
+            .optional()?
+            .is_some())
+    }
+
+    fn read(&self, session_id: &str) -> Result<SessionData> {
+        let data = self
+            .conn
+            .query_row(
+                "SELECT created_at, updated_at, title, provider, model, cwd, message_count,
+                        token_count, parent, branch_point
+                 FROM sessions WHERE id = ?1",
+                [session_id],
+                |row| {
+                    Ok(SessionData {
+                        version: SCHEMA_VERSION,
+                        id: session_id.to_string(),
+                        messages: Vec::new(),
+                        created_at: row.get(0)?,
+                        updated_at: row.get(1)?,
+                        title: row.get(2)?,
+                        provider: row.get(3)?,
+                        model: row.get(4)?,
+                        cwd: row.get(5)?,
+                        message_count: count(row, 6)?,
+                        token_count: count(row, 7)?,
+                        parent: row.get(8)?,
+                        branch_point: row.get::<_, Option<i64>>(9)?.map(|n| n as usize),
+                        tags: Vec::new(),
+                    })
+                },
+            )
+            .optional()?;
+        let mut data = data.ok_or_else(|| anyhow::anyhow!("Session '{}' not found", session_id))?;
+        data.messages = self.read_messages(session_id)?;
+        data.tags = self.read_tags(session_id)?;
+        Ok(data)
+    }
+
+    fn write(&self, data: &SessionData) -> Result<()> {
+        validate_session_name(&data.id)?;
+        // One transaction, so a session is never stored with half its messages
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM messages WHERE session_id = ?1", [&data.id])?;
+        tx.execute("DELETE FROM tags WHERE session_id = ?1", [&data.id])?;
+        tx.execute(
+            "INSERT OR REPLACE INTO sessions (id, created_at, updated_at, title, provider, model,
+                 cwd, message_count, token_count, parent, branch_point)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                data.id,
+                data.created_at,
+                data.updated_at,
+                data.title,
+                data.provider,
+                data.model,
+                data.cwd,
+                data.message_count as i64,
+                data.token_count as i64,
+                data.parent,
+                data.branch_point.map(|n| n as i64),
+            ],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO messages (session_id, seq, id, role, content, timestamp, tool_name,
+                     token_count, pinned, final_answer)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for (seq, message) in data.messages.iter().enumerate() {
+                stmt.execute(params![
+                    data.id,
+                    seq as i64,
+                    message.metadata.id,
+                    role_name(&message.role),
+                    message.content,
+                    message.metadata.timestamp,
+                    message.metadata.tool_name,
+                    message.metadata.token_count as i64,
+                    message.metadata.pinned,
+                    message.metadata.final_answer,
+                ])?;
+            }
+            let mut stmt =
+                tx.prepare("INSERT OR IGNORE INTO tags (session_id, tag) VALUES (?1, ?2)")?;
+            for tag in &data.tags {
+                stmt.execute([&data.id, tag])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, session_id: &str) -> Result<bool> {
+        // Messages go with their session through ON DELETE CASCADE
+        let deleted = self
+            .conn
+            .execute("DELETE FROM sessions WHERE id = ?1", [session_id])?;
+        Ok(deleted > 0)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        validate_session_name(to)?;
+        if self.exists(to)? {
+            return Err(anyhow::anyhow!("Session '{}' already exists", to));
+        }
+        if !self.exists(from)? {
+            return Err(anyhow::anyhow!("Session '{}' not found", from));
+        }
+        // Copy the session row first so the messages' foreign key stays valid
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO sessions (id, created_at, updated_at, title, provider, model, cwd,
+                 message_count, token_count, parent, branch_point)
+             SELECT ?2, created_at, updated_at, title, provider, model, cwd, message_count,
+                 token_count, parent, branch_point
+             FROM sessions WHERE id = ?1",
+            [from, to],
+        )?;
+        tx.execute(
+            "UPDATE messages SET session_id = ?2 WHERE session_id = ?1",
+            [from, to],
+        )?;
+        tx.execute(
+            "UPDATE tags SET session_id = ?2 WHERE session_id = ?1",
+            [from, to],
+        )?;
+        tx.execute("DELETE FROM sessions WHERE id = ?1", [from])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT id FROM sessions")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(ids)
+    }
+
+    fn entries(&self) -> Result<Vec<SessionEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, title, provider, model, cwd, updated_at, message_count, token_count,
+                 (SELECT COALESCE(SUM(LENGTH(CAST(content AS BLOB))), 0)
+                  FROM messages WHERE session_id = sessions.id)
+             FROM sessions",
+        )?;
+        let entries = stmt
+            .query_map([], |row| {
+                Ok(SessionEntry {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    provider: row.get(2)?,
+                    model: row.get(3)?,
+                    cwd: row.get(4)?,
+                    updated_at: row.get(5)?,
+                    messages: count(row, 6)?,
+                    tokens: count(row, 7)?,
+                    bytes: row.get::<_, i64>(8)? as u64,
+                    autosaved: false,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    // Matches like the file index. LIKE only narrows the rows down: it sees
+    // substrings and folds ASCII case only, so non-ASCII words are left to
+    // `matches`, which decides.
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let terms = tokenize(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let prefilter: Vec<&String> = terms.iter().filter(|t| t.is_ascii()).collect();
+        let conditions: String = (1..=prefilter.len())
+            .map(|i| format!(" AND m.content LIKE ?{} ESCAPE '\\'", i))
+            .collect();
+        let sql = format!(
+            "SELECT m.session_id, s.title, m.seq, m.role, m.content
+             FROM messages m JOIN sessions s ON s.id = m.session_id
+             WHERE m.role != 'system'{}
+             ORDER BY s.updated_at DESC, m.seq",
+            conditions
+        );
+        let patterns = prefilter.iter().map(|t| {
+            format!(
+                "%{}%",
+                t.replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_")
+            )
+        });
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(patterns), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                count(row, 2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+        let mut hits = Vec::new();
+        for row in rows {
+            if hits.len() >= limit {
+                break;
+            }
+            let (session_id, title, turn, role, content) = row?;
+            if !matches(&content, &terms) {
+                continue;
+            }
+            hits.push(SearchHit {
+                session_id,
+                title,
+                turn,
+                role: parse_role(&role)?,
+                snippet: snippet(&content, query),
+            });
+        }
+        Ok(hits)
+    }
+}
+
+// Counts and indices are stored as SQLite's signed 64-bit integers
+fn count(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<usize> {
+    Ok(row.get::<_, i64>(idx)? as usize)
+}
+
+fn role_name(role: &Role) -> &'static str {
+    match role {
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::System => "system",
+        Role::Tool => "tool",
+    }
+}
+
+fn parse_role(role: &str) -> Result<Role> {
+    match role {
+        "user" => Ok(Role::User),
+        "assistant" => Ok(Role::Assistant),
+        "system" => Ok(Role::System),
+        "tool" => Ok(Role::Tool),
+        _ => Err(anyhow::anyhow!("Unknown message role: {}", role)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SessionManager;
+    use super::*;
+    use crate::context::ContextManager;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sqlite_store() {
+        let dir = tempdir().unwrap();
+        let store = Box::new(SqliteStore::open(&dir.path().join("sessions.db")).unwrap());
+        let mut manager = SessionManager::new(dir.path().to_path_buf(), store);
+        let mut ctx = ContextManager::new(1000);
+        ctx.add_message(Message::new(Role::System, "You are a bot"));
+        ctx.add_message(Message::new(Role::User, "Find the 100% coverage report"));
+        ctx.add_message(Message::tool("bash", "coverage.html"));
+        ctx.pin(1).unwrap();
+        manager.save_session("sql", &ctx).unwrap();
+        manager.set_session_title("sql", "Coverage").unwrap();
+        let tags = manager
+            .update_session_tags("sql", &["good".to_string(), "sql".to_string()], &[])
+            .unwrap();
+        assert_eq!(tags, ["good", "sql"]);
+        assert!(manager.save_session("../sql", &ctx).is_err());
+
+        // Everything lives in the database, not in per-session files
+        assert!(!dir.path().join("sql.json").exists());
+        let mut loaded = ContextManager::new(1000);
+        manager.load_session("sql", &mut loaded).unwrap();
+        let history = loaded.get_history();
+        assert_eq!(history.len(), 3);
+        assert!(history[1].metadata.pinned);
+        assert_eq!(history[2].role, Role::Tool);
+        assert_eq!(history[2].metadata.tool_name.as_deref(), Some("bash"));
+        assert_eq!(history[1].metadata.id, ctx.get_history()[1].metadata.id);
+
+        let entries = manager.list_recent_sessions().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title.as_deref(), Some("Coverage"));
+        assert_eq!(entries[0].messages, 3);
+
+        // Branching works the same on top of any store
+        ctx.add_message(Message::new(Role::Assistant, "Found it"));
+        manager.fork_session("sql-1", Some("sql"), &ctx).unwrap();
+        assert_eq!(manager.list_branches("sql").unwrap().len(), 2);
+
+        let hits = manager.search("100% COVERAGE", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].turn, 1);
+        assert!(manager.search("system bot", 10).unwrap().is_empty());
+
+        manager.rename_session("sql-1", "sql-2").unwrap();
+        let mut renamed = ContextManager::new(1000);
+        manager.load_session("sql-2", &mut renamed).unwrap();
+        assert_eq!(renamed.get_history().len(), 4);
+        // Tags are saved, inherited by forks and follow renames
+        assert_eq!(manager.session_data("sql-2").unwrap().tags, ["good", "sql"]);
+        manager.delete_session("sql").unwrap();
+        assert_eq!(manager.list_sessions().unwrap(), ["sql-2"]);
+    }
+}
@@ -0,0 +1,263 @@
+use super::crypto::{self, SessionCipher};
+use super::search::{SearchHit, SearchIndex, file_stamp, search_files};
+use super::sqlite::SqliteStore;
+use super::{SessionData, SessionEntry, migrate, validate_session_name, write_atomic};
+use crate::llm::estimate_tokens;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Full-text search index, kept outside the top level so it isn't listed as a session
+const SEARCH_INDEX_FILE: &str = "index/search.json";
+// Rolling backups of overwritten session files
+const BACKUP_DIR: &str = "backups";
+// Database file of the SQLite backend, inside the session directory
+const SQLITE_FILE: &str = "sessions.db";
+
+// Where saved sessions are kept. Autosaved transcripts are not part of the
+// store; they are always plain files next to it.
+pub trait SessionStore {
+    fn exists(&self, session_id: &str) -> Result<bool>;
+    fn read(&self, session_id: &str) -> Result<SessionData>;
+    fn write(&self, data: &SessionData) -> Result<()>;
+    // Returns false if there was no such session
+    fn delete(&self, session_id: &str) -> Result<bool>;
+    // Move a session to a new id; the target must not exist
+    fn rename(&self, from: &str, to: &str) -> Result<()>;
+    // Ids of all saved sessions, in no particular order
+    fn list(&self) -> Result<Vec<String>>;
+    // Listing metadata of all saved sessions, in no particular order
+    fn entries(&self) -> Result<Vec<SessionEntry>>;
+    // Non-system turns matching `query` as `search::matches` does
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>>;
+}
+
+// Factory for the storage backends selectable with --session-store
+pub fn open_store(
+    kind: &str,
+    dir: &Path,
+    backups: usize,
+    cipher: Option<SessionCipher>,
+) -> Result<Box<dyn SessionStore>> {
+    match kind.to_lowercase().as_str() {
+        "dir" | "directory" => Ok(Box::new(
+            DirectoryStore::new(dir.to_path_buf(), backups).with_cipher(cipher),
+        )),
+        // Encrypting columns would defeat the SQL listing and search queries
+        "sqlite" if cipher.is_some() => Err(anyhow::anyhow!(
+            "Session encryption is only supported by the dir session store"
+        )),
+        "sqlite" => Ok(Box::new(SqliteStore::open(&dir.join(SQLITE_FILE))?)),
+        _ => Err(anyhow::anyhow!("Unknown session store: {}", kind)),
+    }
+}
+
+// One pretty-printed `<id>.json` file per session
+pub struct DirectoryStore {
+    dir: PathBuf,
+    // Previous versions kept when a session file is overwritten
+    backups: usize,
+    cipher: Option<SessionCipher>,
+}
+
+impl DirectoryStore {
+    pub fn new(dir: PathBuf, backups: usize) -> Self {
+        Self {
+            dir,
+            backups,
+            cipher: None,
+        }
+    }
+
+    pub fn with_cipher(mut self, cipher: Option<SessionCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    // Path of a saved session; rejects names that would escape the storage dir
+    fn session_file(&self, session_id: &str) -> Result<PathBuf> {
+        validate_session_name(session_id)?;
+        Ok(self.dir.join(format!("{}.json", session_id)))
+    }
+
+    // Keep the previous versions as `backups/<id>.json.1` (newest) to `.<n>`
+    fn rotate_backups(&self, session_id: &str, path: &Path) -> Result<()> {
+        let dir = self.dir.join(BACKUP_DIR);
+        fs::create_dir_all(&dir)?;
+        let backup = |n: usize| dir.join(format!("{}.json.{}", session_id, n));
+        for n in (1..self.backups).rev() {
+            if backup(n).exists() {
+                fs::rename(backup(n), backup(n + 1))?;
+            }
+        }
+        fs::copy(path, backup(1))?;
+        Ok(())
+    }
+
+    fn backup_files(&self, session_id: &str) -> Result<Vec<(usize, PathBuf)>> {
+        let dir = self.dir.join(BACKUP_DIR);
+        let mut backups = Vec::new();
+        if !dir.exists() {
+            return Ok(backups);
+        }
+        let prefix = format!("{}.json.", session_id);
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if let Some(n) = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.strip_prefix(&prefix))
+                .and_then(|n| n.parse::<usize>().ok())
+            {
+                backups.push((n, path));
+            }
+        }
+        Ok(backups)
+    }
+}
+
+impl SessionStore for DirectoryStore {
+    fn exists(&self, session_id: &str) -> Result<bool> {
+        Ok(self.session_file(session_id)?.exists())
+    }
+
+    fn read(&self, session_id: &str) -> Result<SessionData> {
+        let data = fs::read(self.session_file(session_id)?)?;
+        let what = format!("Session '{}'", session_id);
+        let data = crypto::decode(self.cipher.as_ref(), data, &what)?;
+        // Files are upgraded in memory; the new version is written on the next save
+        let value = migrate::migrate(serde_json::from_slice(&data)?)
+            .map_err(|e| anyhow::anyhow!("Session '{}': {}", session_id, e))?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn write(&self, data: &SessionData) -> Result<()> {
+        let path = self.session_file(&data.id)?;
+        if self.backups > 0 && path.exists() {
+            self.rotate_backups(&data.id, &path)?;
+        }
+        let contents = crypto::encode(self.cipher.as_ref(), serde_json::to_vec_pretty(data)?)?;
+        write_atomic(&path, &contents)?;
+        // A stale index entry is refreshed on the next search, so failures only cost speed
+        let mut index = SearchIndex::open(self.dir.join(SEARCH_INDEX_FILE), self.cipher.clone());
+        let indexed = file_stamp(&path).and_then(|stamp| {
+            index.update(data, stamp);
+            index.save()
+        });
+        if let Err(e) = indexed {
+            log::warn!("Failed to update search index for {}: {}", data.id, e);
+        }
+        Ok(())
+    }
+
+    fn delete(&self, session_id: &str) -> Result<bool> {
+        let path = self.session_file(session_id)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(path)?;
+        for (_, backup) in self.backup_files(session_id)? {
+            fs::remove_file(backup)?;
+        }
+        Ok(true)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let target = self.session_file(to)?;
+        if target.exists() {
+            return Err(anyhow::anyhow!("Session '{}' already exists", to));
+        }
+        let mut data = self.read(from)?;
+        data.id = to.to_string();
+        self.write(&data)?;
+        fs::remove_file(self.session_file(from)?)?;
+        for (n, backup) in self.backup_files(from)? {
+            fs::rename(
+                backup,
+                self.dir.join(BACKUP_DIR).join(format!("{}.json.{}", to, n)),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let mut sessions = Vec::new();
+        if self.dir.exists() {
+            for entry in fs::read_dir(&self.dir)? {
+                let entry = entry?;
+                if let Some(name) = entry
+                    .file_name()
+                    .to_str()
+                    .filter(|name| name.ends_with(".json"))
+                {
+                    sessions.push(name.trim_end_matches(".json").to_string());
+                }
+            }
+        }
+        Ok(sessions)
+    }
+
+    fn entries(&self) -> Result<Vec<SessionEntry>> {
+        let mut entries = Vec::new();
+        for id in self.list()? {
+            let data = match self.read(&id) {
+                Ok(data) => data,
+                Err(e) => {
+                    log::warn!("Skipping unreadable session {}: {}", id, e);
+                    continue;
+                }
+            };
+            let metadata = fs::metadata(self.session_file(&id)?)?;
+            // Sessions saved before updated_at existed fall back to the file time
+            let updated_at = if data.updated_at.is_empty() {
+                let modified: chrono::DateTime<chrono::Utc> = metadata.modified()?.into();
+                modified.to_rfc3339()
+            } else {
+                data.updated_at
+            };
+            // Backups count towards the size budget too
+            let backup_bytes: u64 = self
+                .backup_files(&id)?
+                .iter()
+                .filter_map(|(_, path)| fs::metadata(path).ok())
+                .map(|m| m.len())
+                .sum();
+            entries.push(SessionEntry {
+                id,
+                title: data.title,
+                provider: data.provider,
+                model: data.model,
+                cwd: data.cwd,
+                updated_at,
+                messages: data.messages.len(),
+                tokens: data
+                    .messages
+                    .iter()
+                    .map(|m| estimate_tokens(&m.content))
+                    .sum(),
+                bytes: metadata.len() + backup_bytes,
+                autosaved: false,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let sources: Vec<(String, PathBuf)> = self
+            .list()?
+            .into_iter()
+            .map(|id| {
+                let path = self.dir.join(format!("{}.json", id));
+                (id, path)
+            })
+            .collect();
+        search_files(
+            self.dir.join(SEARCH_INDEX_FILE),
+            self.cipher.clone(),
+            &sources,
+            |id| self.read(id),
+            query,
+            limit,
+        )
+    }
+}
@@ -0,0 +1,225 @@
+use super::crypto::{self, SessionCipher};
+use crate::llm::Message;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+// Recorded when the conversation is rewound: messages after `rewind_to`
+// were discarded, and all of them when it is None
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RewindMarker {
+    rewind_to: Option<String>,
+}
+
+// Recorded when compression rewrites the conversation: it continues with
+// exactly these messages, in this order
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReplaceMarker {
+    replaced_by: Vec<String>,
+}
+
+// Append-only JSONL transcript of one session. Every message is written and
+// synced as soon as it is recorded, so a crash loses at most the message in flight.
+pub struct TranscriptWriter {
+    session_id: String,
+    path: PathBuf,
+    file: File,
+    written: HashSet<String>,
+    // Each line is encrypted on its own so the transcript stays append-only
+    cipher: Option<SessionCipher>,
+}
+
+impl TranscriptWriter {
+    // Create the transcript of `session_id`, or reopen it to continue a
+    // resumed session; messages already in it are not written again
+    pub fn create(dir: &Path, session_id: &str, cipher: Option<SessionCipher>) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = transcript_path(dir, session_id);
+        let mut written = HashSet::new();
+        if path.exists() {
+            // Drop a torn last line so appended messages start on a line of their own
+            let data = fs::read(&path)?;
+            let end = data.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+            if end < data.len() {
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(end as u64)?;
+            }
+            written = read_transcript(&path, cipher.as_ref())?
+                .into_iter()
+                .map(|m| m.metadata.id)
+                .filter(|id| !id.is_empty())
+                .collect();
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open transcript {}", path.display()))?;
+        Ok(Self {
+            session_id: session_id.to_string(),
+            path,
+            file,
+            written,
+            cipher,
+        })
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Append messages not yet in the transcript; already written ones are skipped by id
+    pub fn append_new(&mut self, messages: &[Message]) -> Result<usize> {
+        let mut count = 0;
+        for message in messages {
+            let id = &message.metadata.id;
+            if !id.is_empty() && self.written.contains(id) {
+                continue;
+            }
+            let line = crypto::encode(self.cipher.as_ref(), serde_json::to_vec(message)?)?;
+            self.file.write_all(&line)?;
+            self.file.write_all(b"\n")?;
+            if !id.is_empty() {
+                self.written.insert(id.clone());
+            }
+            count += 1;
+        }
+        if count > 0 {
+            self.file.sync_data()?;
+        }
+        Ok(count)
+    }
+
+    // Record that the conversation was cut back to `kept`, so readers drop
+    // the messages written after it
+    pub fn rewind(&mut self, kept: &[Message]) -> Result<()> {
+        self.append_new(kept)?;
+        let marker = RewindMarker {
+            rewind_to: kept
+                .iter()
+                .rev()
+                .map(|m| m.metadata.id.clone())
+                .find(|id| !id.is_empty()),
+        };
+        let line = crypto::encode(self.cipher.as_ref(), serde_json::to_vec(&marker)?)?;
+        self.file.write_all(&line)?;
+        self.file.write_all(b"\n")?;
+        self.file.sync_data()?;
+        self.written
+            .retain(|id| kept.iter().any(|m| &m.metadata.id == id));
+        Ok(())
+    }
+
+    // Record that the conversation was rewritten to `history`, e.g. when
+    // compression replaced older messages with a summary
+    pub fn replace(&mut self, history: &[Message]) -> Result<()> {
+        self.append_new(history)?;
+        let marker = ReplaceMarker {
+            replaced_by: history
+                .iter()
+                .map(|m| m.metadata.id.clone())
+                .filter(|id| !id.is_empty())
+                .collect(),
+        };
+        let line = crypto::encode(self.cipher.as_ref(), serde_json::to_vec(&marker)?)?;
+        self.file.write_all(&line)?;
+        self.file.write_all(b"\n")?;
+        self.file.sync_data()?;
+        self.written.retain(|id| marker.replaced_by.contains(id));
+        Ok(())
+    }
+}
+
+// New session ids sort by start time
+pub fn generate_session_id() -> String {
+    let suffix = Uuid::new_v4()
+        .to_string()
+        .split('-')
+        .next()
+        .unwrap_or("")
+        .to_string();
+    format!(
+        "{}-{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        suffix
+    )
+}
+
+pub fn transcript_path(dir: &Path, session_id: &str) -> PathBuf {
+    dir.join(format!("{}.jsonl", session_id))
+}
+
+// Read a transcript back. A torn last line from a crash is skipped, and
+// messages discarded by a rewind or replaced by compression are left out.
+pub fn read_transcript(path: &Path, cipher: Option<&SessionCipher>) -> Result<Vec<Message>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open transcript {}", path.display()))?;
+    let lines = BufReader::new(file)
+        .lines()
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let mut messages = Vec::new();
+    for (n, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let what = format!("transcript {}", path.display());
+        let line = match crypto::decode(cipher, line.clone().into_bytes(), &what) {
+            Ok(line) => line,
+            // Only a torn last line is expected; anything else is a key problem
+            Err(e) if n + 1 < lines.len() || messages.is_empty() => return Err(e),
+            Err(e) => {
+                log::warn!("Skipping unreadable last line of {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        if let Ok(marker) = serde_json::from_slice::<RewindMarker>(&line) {
+            let keep = match marker.rewind_to {
+                Some(id) => match messages.iter().rposition(|m: &Message| m.metadata.id == id) {
+                    Some(i) => i + 1,
+                    None => {
+                        log::warn!("Ignoring rewind to unknown message in {}", path.display());
+                        messages.len()
+                    }
+                },
+                None => 0,
+            };
+            messages.truncate(keep);
+            continue;
+        }
+        if let Ok(marker) = serde_json::from_slice::<ReplaceMarker>(&line) {
+            let mut by_id: HashMap<String, Message> = messages
+                .drain(..)
+                .map(|m| (m.metadata.id.clone(), m))
+                .collect();
+            for id in marker.replaced_by {
+                match by_id.remove(&id) {
+                    Some(m) => messages.push(m),
+                    None => log::warn!("Ignoring unknown message {} in {}", id, path.display()),
+                }
+            }
+            continue;
+        }
+        match serde_json::from_slice::<Message>(&line) {
+            Ok(m) => messages.push(m),
+            Err(e) => log::warn!(
+                "Skipping unreadable line {} of {}: {}",
+                n + 1,
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(messages)
+}
@@ -0,0 +1,217 @@
+use crate::session::crypto::{self, SessionCipher};
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use uuid::Uuid;
+
+pub const DEFAULT_MAX_OUTPUT_CHARS: usize = 4000;
+
+// Stores oversized tool outputs on disk so only a head/tail excerpt has to
+// stay in the context. Each stored output is addressed by a short handle.
+pub struct SpillStore {
+    // Follows the session the conversation belongs to, see `set_dir`
+    dir: Mutex<PathBuf>,
+    max_chars: usize,
+    // Encrypts stored outputs like the session files they belong to
+    cipher: Option<SessionCipher>,
+}
+
+impl SpillStore {
+    pub fn new(dir: PathBuf, max_chars: usize) -> Result<Self> {
+        create_dir(&dir)?;
+        Ok(Self {
+            dir: Mutex::new(dir),
+            max_chars,
+            cipher: None,
+        })
+    }
+
+    pub fn with_cipher(mut self, cipher: Option<SessionCipher>) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    // Outputs longer than this many characters are elided
+    pub fn max_chars(&self) -> usize {
+        self.max_chars
+    }
+
+    // Switch to the outputs of another session, e.g. after loading it
+    pub fn set_dir(&self, dir: PathBuf) -> Result<()> {
+        create_dir(&dir)?;
+        *self.dir.lock().unwrap() = dir;
+        Ok(())
+    }
+
+    // Store the full output and return its handle. Identical outputs get the
+    // same handle, so their elided excerpts match and can be deduplicated.
+    pub fn store(&self, tool_name: &str, output: &str) -> Result<String> {
+        let prefix: String = tool_name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        let prefix = if prefix.is_empty() {
+            "out".to_string()
+        } else {
+            prefix
+        };
+        let mut hasher = DefaultHasher::new();
+        output.hash(&mut hasher);
+        let mut handle = format!("{}-{:016x}", prefix, hasher.finish());
+        if self.path_for(&handle)?.exists() {
+            match self.read(&handle) {
+                Ok(stored) if stored == output => return Ok(handle),
+                // A hash collision, or a file this store cannot read
+                _ => {
+                    let suffix = Uuid::new_v4()
+                        .to_string()
+                        .split('-')
+                        .next()
+                        .unwrap_or("")
+                        .to_string();
+                    handle = format!("{}-{}", prefix, suffix);
+                }
+            }
+        }
+        let data = crypto::encode(self.cipher.as_ref(), output.as_bytes().to_vec())?;
+        fs::write(self.path_for(&handle)?, data)
+            .with_context(|| format!("Failed to store tool output '{}'", handle))?;
+        Ok(handle)
+    }
+
+    pub fn read(&self, handle: &str) -> Result<String> {
+        let data = fs::read(self.path_for(handle)?)
+            .with_context(|| format!("No stored output for handle '{}'", handle))?;
+        let what = format!("Tool output '{}'", handle);
+        let data = crypto::decode(self.cipher.as_ref(), data, &what)?;
+        String::from_utf8(data).with_context(|| format!("{} is not valid UTF-8", what))
+    }
+
+    // Return the output unchanged if it fits, otherwise store it and return
+    // its head and tail with a note pointing at the handle.
+    pub fn elide(&self, tool_name: &str, output: String) -> String {
+        if output.chars().count() <= self.max_chars {
+            return output;
+        }
+        let handle = match self.store(tool_name, &output) {
+            Ok(h) => h,
+            Err(e) => {
+                log::warn!("Failed to spill output of tool '{}': {}", tool_name, e);
+                return output;
+            }
+        };
+
+        let head = take_head(&output, self.max_chars / 2);
+        let tail = take_tail(&output, self.max_chars / 4);
+        let total_lines = output.lines().count();
+        let elided_lines = total_lines
+            .saturating_sub(head.lines().count())
+            .saturating_sub(tail.lines().count());
+        format!(
+            "{}\n... [{} lines elided, {} chars total. Full output stored as '{}'; use the tool_output tool with this handle to page through or grep it] ...\n{}",
+            head.trim_end_matches('\n'),
+            elided_lines,
+            output.chars().count(),
+            handle,
+            tail.trim_start_matches('\n')
+        )
+    }
+
+    fn path_for(&self, handle: &str) -> Result<PathBuf> {
+        if handle.is_empty()
+            || !handle
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(anyhow::anyhow!("Invalid output handle: '{}'", handle));
+        }
+        Ok(self.dir.lock().unwrap().join(format!("{}.txt", handle)))
+    }
+}
+
+fn create_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create tool output directory {}", dir.display()))
+}
+
+// At most `max_chars` from the start, cut back to a line boundary when possible
+fn take_head(text: &str, max_chars: usize) -> &str {
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    match text[..end].rfind('\n') {
+        Some(i) if i > 0 => &text[..=i],
+        _ => &text[..end],
+    }
+}
+
+// At most `max_chars` from the end, cut forward to a line boundary when possible
+fn take_tail(text: &str, max_chars: usize) -> &str {
+    let total = text.chars().count();
+    let start = text
+        .char_indices()
+        .nth(total.saturating_sub(max_chars))
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    match text[start..].find('\n') {
+        Some(i) if start + i + 1 < text.len() => &text[start + i + 1..],
+        _ => &text[start..],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_elide_large_output() {
+        let dir = tempdir().unwrap();
+        let store = SpillStore::new(dir.path().to_path_buf(), 200).unwrap();
+
+        let small = "short output".to_string();
+        assert_eq!(store.elide("bash", small.clone()), small);
+
+        let big = (0..100)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let elided = store.elide("bash", big.clone());
+        assert!(elided.starts_with("line 0\n"));
+        assert!(elided.ends_with("line 99"));
+        assert!(!elided.contains("line 50\n"));
+
+        let re = regex::Regex::new(r"stored as '([\w-]+)'").unwrap();
+        let handle = re.captures(&elided).unwrap()[1].to_string();
+        assert!(handle.starts_with("bash-"));
+        assert_eq!(store.read(&handle).unwrap(), big);
+        assert!(store.read("../escape").is_err());
+
+        // The same output elides to the same text, so it can be deduplicated
+        assert_eq!(store.elide("bash", big.clone()), elided);
+        assert_ne!(store.store("bash", "other").unwrap(), handle);
+    }
+
+    #[test]
+    fn test_encrypted_spill() {
+        let dir = tempdir().unwrap();
+        let cipher = SessionCipher::from_key_str(&"22".repeat(32)).unwrap();
+        let store = SpillStore::new(dir.path().to_path_buf(), 10)
+            .unwrap()
+            .with_cipher(Some(cipher));
+        let handle = store.store("bash", "secret tool output").unwrap();
+
+        let on_disk = fs::read(dir.path().join(format!("{}.txt", handle))).unwrap();
+        assert!(crypto::is_encrypted(&on_disk));
+        assert!(!String::from_utf8_lossy(&on_disk).contains("secret"));
+        assert_eq!(store.read(&handle).unwrap(), "secret tool output");
+
+        let plain = SpillStore::new(dir.path().to_path_buf(), 10).unwrap();
+        assert!(plain.read(&handle).is_err());
+    }
+}
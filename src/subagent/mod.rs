@@ -106,10 +106,10 @@ impl SubAgent {
         );
         self.context.set_llm(llm.clone());
         // Add task message into context
-        self.context.add_message(crate::llm::Message {
-            role: crate::llm::Role::User,
-            content: self.task.clone(),
-        });
+        self.context.add_message(crate::llm::Message::new(
+            crate::llm::Role::User,
+            self.task.clone(),
+        ));
 
         self.status = SubAgentStatus::Running;
         let mut loop_count = 0;
@@ -134,10 +134,10 @@ impl SubAgent {
                 }
                 None => llm.complete(self.context.get_history()).await?,
             };
-            self.context.add_message(crate::llm::Message {
-                role: crate::llm::Role::Assistant,
-                content: response.clone(),
-            });
+            self.context.add_message(crate::llm::Message::new(
+                crate::llm::Role::Assistant,
+                response.clone(),
+            ));
 
             // Check for tool calls
             if let Some(tool_call) = parse_tool_call(&response) {
@@ -184,11 +184,10 @@ impl SubAgent {
                     self.id, tool_call.name, output
                 );
 
-                // Add tool output into context
-                self.context.add_message(crate::llm::Message {
-                    role: crate::llm::Role::User,
-                    content: format!("Tool '{}' output:\n{}", tool_call.name, output),
-                });
+                // Add tool output into context, eliding it if too large
+                let output = tool_registry.limit_output(&tool_call.name, output);
+                self.context
+                    .add_message(crate::llm::Message::tool(&tool_call.name, output));
             } else {
                 // No tool call. If model indicates completion, stop. Otherwise continue.
                 if let Some(final_text) = extract_final(&response) {
@@ -204,11 +203,11 @@ impl SubAgent {
                     return Err(anyhow::anyhow!("SubAgent {} max loops reached", self.id));
                 }
 
-                self.context.add_message(crate::llm::Message {
-                    role: crate::llm::Role::User,
-                    content: "Continue. If finished, wrap the final answer in <final>...</final>."
+                self.context.add_message(crate::llm::Message::new(
+                    crate::llm::Role::User,
+                    "Continue. If finished, wrap the final answer in <final>...</final>."
                         .to_string(),
-                });
+                ));
             }
 
             // Try to compress context to avoid unbounded growth
@@ -225,6 +224,8 @@ pub struct SubAgentManager {
     agents: Arc<Mutex<HashMap<String, Arc<AsyncMutex<SubAgent>>>>>,
     llm: Arc<dyn LLM>,
     shared_tool_registry: Arc<ToolRegistry>,
+    summarizer: Option<Arc<dyn LLM>>,
+    summary_prompt: Option<String>,
 }
 
 impl SubAgentManager {
@@ -233,20 +234,36 @@ impl SubAgentManager {
             agents: Arc::new(Mutex::new(HashMap::new())),
             llm,
             shared_tool_registry: tool_registry,
+            summarizer: None,
+            summary_prompt: None,
         }
     }
 
+    // Model used for SubAgent context summaries
+    pub fn set_summarizer(&mut self, summarizer: Arc<dyn LLM>) {
+        self.summarizer = Some(summarizer);
+    }
+
+    // Custom summary instructions for SubAgent contexts
+    pub fn set_summary_prompt(&mut self, prompt: String) {
+        self.summary_prompt = Some(prompt);
+    }
+
     // Create a new SubAgent and return its ID
     pub fn spawn(&mut self, config: SubAgentConfig) -> Result<String> {
         let mut agent = SubAgent::new(config);
         agent.set_llm(self.llm.clone());
+        if let Some(summarizer) = &self.summarizer {
+            agent.context.set_summarizer(summarizer.clone());
+        }
+        if let Some(prompt) = &self.summary_prompt {
+            agent.context.set_summary_prompt(prompt.clone());
+        }
 
         // Inject system prompt by agent type
-        let mut prompt = Self::generate_system_prompt(&agent.agent_type);
-        prompt.push_str("\n\n");
-        prompt.push_str("When you are finished, wrap the final answer in <final>...</final>.\n");
-        prompt.push_str("If you need more steps and no tool call is required, continue until you are ready to finalize.\n\n");
-        prompt.push_str(&self.shared_tool_registry.generate_tool_instructions());
+        let prompt = self
+            .shared_tool_registry
+            .generate_subagent_prompt(&agent.agent_type, &agent.task);
         agent.inject_system_prompt(prompt);
 
         let id = agent.id.clone();
@@ -274,81 +291,6 @@ impl SubAgentManager {
             }
         }
     }
-
-    // Generate system prompt for a given type
-    fn generate_system_prompt(agent_type: &str) -> String {
-        match agent_type.to_lowercase().as_str() {
-            "code" => Self::code_agent_prompt(),
-            "test" => Self::test_agent_prompt(),
-            "doc" => Self::doc_agent_prompt(),
-            "analysis" => Self::analysis_agent_prompt(),
-            _ => Self::dynamic_agent_prompt(),
-        }
-    }
-
-    fn code_agent_prompt() -> String {
-        String::from(
-            "You are a Code SubAgent focused on code implementation, refactoring, and optimization.\n\
-            Guidelines:\n\
-            - Write clean, idiomatic code\n\
-            - Follow existing code patterns and conventions\n\
-            - Add comments for complex logic\n\
-            - Consider edge cases and error handling\n\
-            - Run tests to verify your changes\n\n\
-            You have access to bash tool for running commands and testing.",
-        )
-    }
-
-    fn test_agent_prompt() -> String {
-        String::from(
-            "You are a Test SubAgent focused on writing and improving tests.\n\
-            Guidelines:\n\
-            - Write comprehensive unit tests\n\
-            - Cover edge cases and error scenarios\n\
-            - Use appropriate testing frameworks\n\
-            - Ensure tests are fast and isolated\n\
-            - Provide clear test documentation\n\n\
-            You have access to bash tool for running tests.",
-        )
-    }
-
-    fn doc_agent_prompt() -> String {
-        String::from(
-            "You are a Documentation SubAgent focused on creating and improving documentation.\n\
-            Guidelines:\n\
-            - Write clear, concise documentation\n\
-            - Include code examples where appropriate\n\
-            - Document public APIs thoroughly\n\
-            - Keep documentation up-to-date with code changes\n\
-            - Use markdown format for readability\n\n\
-            You have access to bash tool for reading files and checking documentation.",
-        )
-    }
-
-    fn analysis_agent_prompt() -> String {
-        String::from(
-            "You are an Analysis SubAgent focused on understanding and analyzing codebases.\n\
-            Guidelines:\n\
-            - Analyze code structure and architecture\n\
-            - Identify patterns and anti-patterns\n\
-            - Provide insights on code quality\n\
-            - Suggest improvements where needed\n\
-            - Be thorough in your analysis\n\n\
-            You have access to bash tool for exploring the codebase.",
-        )
-    }
-
-    fn dynamic_agent_prompt() -> String {
-        String::from(
-            "You are a general-purpose SubAgent.\n\
-            Guidelines:\n\
-            - Focus on completing the assigned task\n\
-            - Ask for clarification if needed\n\
-            - Provide clear, actionable results\n\
-            - Report any errors or blockers encountered\n\n\
-            You have access to bash tool for executing commands.",
-        )
-    }
 }
 
 // Parse tool call
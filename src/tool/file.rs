@@ -0,0 +1,363 @@
+use super::Tool;
+use crate::session::write_atomic;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::Value;
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Lines returned without an end_line; typical source lines this many fit under
+// the default spill cap (spill::DEFAULT_MAX_OUTPUT_CHARS), so reads are not elided
+const READ_FILE_DEFAULT_LIMIT: usize = 80;
+
+fn path_arg(args: &Value) -> Result<&str> {
+    args["path"]
+        .as_str()
+        .filter(|p| !p.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Missing 'path' argument"))
+}
+
+fn read_text(path: &str) -> Result<String> {
+    let bytes = fs::read(path).map_err(|e| anyhow::anyhow!("Cannot read {}: {}", path, e))?;
+    String::from_utf8(bytes).map_err(|_| anyhow::anyhow!("{} is not a UTF-8 text file", path))
+}
+
+// Replace a file through a temporary copy, so an interrupted write never
+// leaves it truncated. Symlinks are written through and permissions kept.
+fn write_text(path: &str, content: &str) -> Result<()> {
+    let target = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let permissions = fs::metadata(&target).ok().map(|m| m.permissions());
+    write_atomic(&target, content.as_bytes())
+        .map_err(|e| anyhow::anyhow!("Cannot write {}: {}", path, e))?;
+    if let Some(permissions) = permissions {
+        fs::set_permissions(&target, permissions)?;
+    }
+    Ok(())
+}
+
+// Unified diff between two versions of `path`, with three lines of context
+fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+// ReadFileTool - Returns numbered lines of a text file
+pub struct ReadFileTool;
+
+#[async_trait]
+impl Tool for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "Read a text file with line numbers, optionally only a range of lines"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path of the file"
+                },
+                "start_line": {
+                    "type": "integer",
+                    "description": "First line to return, 1-based (default: 1)"
+                },
+                "end_line": {
+                    "type": "integer",
+                    "description": "Last line to return, inclusive (default: 80 lines from start_line)"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let path = path_arg(&args)?;
+        let content = read_text(path)?;
+        let total = content.lines().count();
+        let start = args["start_line"].as_u64().unwrap_or(1).max(1) as usize;
+        let end = match args["end_line"].as_u64() {
+            Some(end) => end as usize,
+            None => start.saturating_add(READ_FILE_DEFAULT_LIMIT - 1),
+        }
+        .min(total);
+        if total == 0 {
+            return Ok(format!("[{}] empty file", path));
+        }
+        if start > end {
+            return Err(anyhow::anyhow!(
+                "Line range {}-{} is outside {} ({} lines)",
+                start,
+                end,
+                path,
+                total
+            ));
+        }
+        let mut result = format!("[{}] lines {}-{} of {}\n", path, start, end, total);
+        for (i, line) in content.lines().enumerate().take(end).skip(start - 1) {
+            result.push_str(&format!("{:>6}  {}\n", i + 1, line));
+        }
+        Ok(result)
+    }
+}
+
+// WriteFileTool - Creates or overwrites a file
+pub struct WriteFileTool;
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn description(&self) -> &str {
+        "Create a file or replace its whole content; returns a diff of the change"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path of the file; missing parent directories are created"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The complete new content"
+                }
+            },
+            "required": ["path", "content"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let path = path_arg(&args)?;
+        let content = args["content"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'content' argument"))?;
+        let existing = Path::new(path).exists();
+        let old = if existing {
+            read_text(path)?
+        } else {
+            String::new()
+        };
+        if existing && old == content {
+            return Ok(format!("No changes to {}", path));
+        }
+        if let Some(dir) = Path::new(path)
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir)?;
+        }
+        write_text(path, content)?;
+        let verb = if existing { "Updated" } else { "Created" };
+        Ok(format!(
+            "{} {}\n{}",
+            verb,
+            path,
+            unified_diff(path, &old, content)
+        ))
+    }
+}
+
+// EditFileTool - Replaces an exact string in a file
+pub struct EditFileTool;
+
+#[async_trait]
+impl Tool for EditFileTool {
+    fn name(&self) -> &str {
+        "edit_file"
+    }
+
+    fn description(&self) -> &str {
+        "Replace an exact string in a file; it must match once unless replace_all is set. Returns a diff of the change"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path of the file"
+                },
+                "old_string": {
+                    "type": "string",
+                    "description": "Text to replace, including whitespace and indentation exactly as in the file"
+                },
+                "new_string": {
+                    "type": "string",
+                    "description": "Replacement text"
+                },
+                "replace_all": {
+                    "type": "boolean",
+                    "description": "Replace every occurrence instead of requiring a unique match (default: false)"
+                }
+            },
+            "required": ["path", "old_string", "new_string"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let path = path_arg(&args)?;
+        let old_string = args["old_string"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Missing 'old_string' argument"))?;
+        let new_string = args["new_string"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'new_string' argument"))?;
+        let replace_all = args["replace_all"].as_bool().unwrap_or(false);
+        if old_string == new_string {
+            return Err(anyhow::anyhow!("old_string and new_string are identical"));
+        }
+
+        let old = read_text(path)?;
+        let lines: Vec<usize> = old
+            .match_indices(old_string)
+            .map(|(i, _)| old[..i].matches('\n').count() + 1)
+            .collect();
+        match lines.len() {
+            0 => {
+                return Err(anyhow::anyhow!(
+                    "old_string not found in {}; it must match exactly, including whitespace and indentation",
+                    path
+                ));
+            }
+            1 => {}
+            n if !replace_all => {
+                let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+                return Err(anyhow::anyhow!(
+                    "old_string matches {} times in {} (lines {}); include more surrounding text to make it unique, or set replace_all",
+                    n,
+                    path,
+                    lines.join(", ")
+                ));
+            }
+            _ => {}
+        }
+        let new = old.replace(old_string, new_string);
+        write_text(path, &new)?;
+        Ok(format!(
+            "Edited {} ({} replacement{})\n{}",
+            path,
+            lines.len(),
+            if lines.len() == 1 { "" } else { "s" },
+            unified_diff(path, &old, &new)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("src/lib.rs");
+        let path = path.to_str().unwrap();
+
+        let created = WriteFileTool
+            .call(serde_json::json!({"path": path, "content": "fn a() {}\nfn b() {}\nfn b() {}\n"}))
+            .await
+            .unwrap();
+        assert!(created.starts_with(&format!("Created {}", path)));
+        assert!(created.contains("+fn a() {}\n"));
+
+        let read = ReadFileTool
+            .call(serde_json::json!({"path": path, "start_line": 2, "end_line": 2}))
+            .await
+            .unwrap();
+        assert_eq!(
+            read,
+            format!("[{}] lines 2-2 of 3\n     2  fn b() {{}}\n", path)
+        );
+        let err = ReadFileTool
+            .call(serde_json::json!({"path": path, "start_line": u64::MAX}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("outside"), "{}", err);
+
+        // Without end_line only the first READ_FILE_DEFAULT_LIMIT lines are returned
+        let long = dir.path().join("long.txt");
+        let text: String = (1..=200).map(|i| format!("line {}\n", i)).collect();
+        fs::write(&long, text).unwrap();
+        let read = ReadFileTool
+            .call(serde_json::json!({"path": long.to_str().unwrap()}))
+            .await
+            .unwrap();
+        assert!(read.contains("lines 1-80 of 200"), "{}", read);
+        assert!(read.ends_with("    80  line 80\n"), "{}", read);
+
+        let edited = EditFileTool
+            .call(serde_json::json!({"path": path, "old_string": "fn a", "new_string": "pub fn a"}))
+            .await
+            .unwrap();
+        assert!(
+            edited.contains("-fn a() {}\n+pub fn a() {}\n"),
+            "{}",
+            edited
+        );
+
+        let err = EditFileTool
+            .call(serde_json::json!({"path": path, "old_string": "fn b", "new_string": "fn c"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("matches 2 times"), "{}", err);
+        assert!(err.to_string().contains("(lines 2, 3)"), "{}", err);
+        let err = EditFileTool
+            .call(serde_json::json!({"path": path, "old_string": "fn z", "new_string": "fn c"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+
+        let edited = EditFileTool
+            .call(serde_json::json!({"path": path, "old_string": "fn b", "new_string": "fn c", "replace_all": true}))
+            .await
+            .unwrap();
+        assert!(edited.contains("(2 replacements)"));
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            "pub fn a() {}\nfn c() {}\nfn c() {}\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_edit_keeps_permissions_and_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("run.sh");
+        fs::write(&script, "echo a\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let link = dir.path().join("link.sh");
+        std::os::unix::fs::symlink(&script, &link).unwrap();
+
+        EditFileTool
+            .call(serde_json::json!({"path": link.to_str().unwrap(), "old_string": "a", "new_string": "b"}))
+            .await
+            .unwrap();
+        assert!(
+            fs::symlink_metadata(&link)
+                .unwrap()
+                .file_type()
+                .is_symlink()
+        );
+        assert_eq!(fs::read_to_string(&script).unwrap(), "echo b\n");
+        let mode = fs::metadata(&script).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        // No temporary files are left behind
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -1,5 +1,16 @@
+mod file;
+mod search;
+
+pub use file::{EditFileTool, ReadFileTool, WriteFileTool};
+pub use search::{GlobTool, GrepTool};
+
+use crate::memory::{MemoryScope, MemoryStore};
+use crate::project::ProjectContext;
+use crate::prompt::PromptTemplates;
+use crate::spill::SpillStore;
 use anyhow::Result;
 use async_trait::async_trait;
+use regex::Regex;
 use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -14,12 +25,54 @@ pub trait Tool: Send + Sync {
 
 pub struct ToolRegistry {
     tools: HashMap<String, Arc<dyn Tool>>,
+    spill_store: Option<Arc<SpillStore>>,
+    memory_store: Option<Arc<MemoryStore>>,
+    project_context: Option<ProjectContext>,
+    prompts: PromptTemplates,
 }
 
 impl ToolRegistry {
     pub fn new() -> Self {
         Self {
             tools: HashMap::new(),
+            spill_store: None,
+            memory_store: None,
+            project_context: None,
+            prompts: PromptTemplates::default(),
+        }
+    }
+
+    // Oversized tool outputs are elided into this store before they reach the context
+    pub fn set_spill_store(&mut self, store: Arc<SpillStore>) {
+        self.spill_store = Some(store);
+    }
+
+    pub fn spill_store(&self) -> Option<&Arc<SpillStore>> {
+        self.spill_store.as_ref()
+    }
+
+    // Long-term memory entries are injected into generated system prompts
+    pub fn set_memory_store(&mut self, store: Arc<MemoryStore>) {
+        self.memory_store = Some(store);
+    }
+
+    // Environment and project instructions are injected into generated system prompts
+    pub fn set_project_context(&mut self, context: ProjectContext) {
+        self.project_context = Some(context);
+    }
+
+    // Templates used for the main and SubAgent system prompts
+    pub fn set_prompt_templates(&mut self, prompts: PromptTemplates) {
+        self.prompts = prompts;
+    }
+
+    // Prepare a tool output for the context, eliding it when it is too large
+    pub fn limit_output(&self, tool_name: &str, output: String) -> String {
+        match &self.spill_store {
+            // Pages of stored outputs are already sized to fit
+            Some(_) if tool_name == "tool_output" => output,
+            Some(store) => store.elide(tool_name, output),
+            None => output,
         }
     }
 
@@ -37,12 +90,68 @@ impl ToolRegistry {
         tools
     }
 
-    pub fn generate_system_prompt(&self) -> String {
-        let mut prompt = String::from("You are a helpful coding agent.\n\n");
-        prompt.push_str("When you are finished, wrap the final answer in <final>...</final>.\n");
-        prompt.push_str("If you need more steps and no tool call is required, continue until you are ready to finalize.\n\n");
-        prompt.push_str(&self.generate_tool_instructions());
-        prompt
+    // `task` is the current user request, used to pick relevant memories
+    pub fn generate_system_prompt(&self, task: &str) -> String {
+        self.prompts.render("system", &self.prompt_vars(task))
+    }
+
+    // System prompt for a SubAgent: the type-specific template wrapped in the
+    // shared `subagent` template. Unknown types use the dynamic template.
+    pub fn generate_subagent_prompt(&self, agent_type: &str, task: &str) -> String {
+        let agent_type = agent_type.to_lowercase();
+        let template = match agent_type.as_str() {
+            "code" | "test" | "doc" | "analysis" => format!("subagent_{}", agent_type),
+            _ => "subagent_dynamic".to_string(),
+        };
+        let mut vars = self.prompt_vars(task);
+        vars.insert("agent_type", agent_type);
+        vars.insert("task", task.to_string());
+        let agent_prompt = self.prompts.render(&template, &vars);
+        vars.insert("agent_prompt", agent_prompt);
+        self.prompts.render("subagent", &vars)
+    }
+
+    // Variables available to every prompt template
+    fn prompt_vars(&self, task: &str) -> HashMap<&'static str, String> {
+        let (cwd, date) = match &self.project_context {
+            Some(context) => (context.cwd.display().to_string(), context.date.clone()),
+            None => (
+                std::env::current_dir()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default(),
+                chrono::Local::now().format("%Y-%m-%d").to_string(),
+            ),
+        };
+        let memory = self.generate_memory_prompt(&format!("{}\n{}", task, cwd));
+        HashMap::from([
+            ("tools", self.generate_tool_instructions()),
+            ("project", self.generate_project_prompt()),
+            ("memory", memory),
+            ("cwd", cwd),
+            ("date", date),
+        ])
+    }
+
+    pub fn generate_project_prompt(&self) -> String {
+        match &self.project_context {
+            Some(context) => format!("{}\n", context.render()),
+            None => String::new(),
+        }
+    }
+
+    // Memories relevant to `query`, e.g. the task and the project path
+    pub fn generate_memory_prompt(&self, query: &str) -> String {
+        match &self.memory_store {
+            Some(store) => {
+                let section = store.prompt_section(query);
+                if section.is_empty() {
+                    section
+                } else {
+                    format!("{}\n", section)
+                }
+            }
+            None => String::new(),
+        }
     }
 
     pub fn generate_tool_instructions(&self) -> String {
@@ -105,6 +214,228 @@ impl Tool for BashTool {
     }
 }
 
+// ToolOutputTool - Pages through or greps tool outputs elided by the SpillStore
+pub struct ToolOutputTool {
+    store: Arc<SpillStore>,
+}
+
+impl ToolOutputTool {
+    pub fn new(store: Arc<SpillStore>) -> Self {
+        Self { store }
+    }
+}
+
+const TOOL_OUTPUT_DEFAULT_LIMIT: usize = 100;
+
+#[async_trait]
+impl Tool for ToolOutputTool {
+    fn name(&self) -> &str {
+        "tool_output"
+    }
+
+    fn description(&self) -> &str {
+        "Read a tool output that was too large for the context, by the handle given in the elided output"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "handle": {
+                    "type": "string",
+                    "description": "Handle of the stored output"
+                },
+                "offset": {
+                    "type": "integer",
+                    "description": "First line to return, 1-based (default: 1)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of lines to return (default: 100)"
+                },
+                "char_offset": {
+                    "type": "integer",
+                    "description": "First character of line `offset` to return, 0-based, to continue a line that was cut (default: 0)"
+                },
+                "pattern": {
+                    "type": "string",
+                    "description": "Regex; if set, only matching lines are returned"
+                }
+            },
+            "required": ["handle"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let handle = args["handle"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'handle' argument"))?;
+        let offset = args["offset"].as_u64().unwrap_or(1).max(1) as usize;
+        let char_offset = args["char_offset"].as_u64().unwrap_or(0) as usize;
+        let limit = args["limit"]
+            .as_u64()
+            .unwrap_or(TOOL_OUTPUT_DEFAULT_LIMIT as u64) as usize;
+        let output = self.store.read(handle)?;
+        let total = output.lines().count();
+
+        let numbered = output.lines().enumerate().map(|(i, l)| (i + 1, l));
+        let selected: Vec<(usize, &str)> = match args["pattern"].as_str() {
+            Some(pattern) => {
+                let re = Regex::new(pattern)?;
+                numbered
+                    .skip(offset - 1)
+                    .filter(|(_, l)| re.is_match(l))
+                    .take(limit)
+                    .collect()
+            }
+            None => numbered.skip(offset - 1).take(limit).collect(),
+        };
+
+        // A page must fit in the context without being elided again, so it
+        // ends early when the lines would exceed the spill limit, and a single
+        // line longer than that is cut
+        let mut lines = String::new();
+        let mut shown: Vec<usize> = Vec::new();
+        // Line that was cut and the character to continue it from
+        let mut cut = None;
+        for (n, line) in &selected {
+            let start = if *n == offset { char_offset } else { 0 };
+            let line: String = line.chars().skip(start).collect();
+            let formatted = format!("{:>6}  {}\n", n, line);
+            if !shown.is_empty()
+                && lines.chars().count() + formatted.chars().count() > self.store.max_chars()
+            {
+                break;
+            }
+            if formatted.chars().count() > self.store.max_chars() {
+                let room = self.store.max_chars().saturating_sub(9).max(1);
+                let part: String = line.chars().take(room).collect();
+                lines.push_str(&format!("{:>6}  {}\n", n, part));
+                shown.push(*n);
+                cut = Some((*n, start + room));
+                break;
+            }
+            lines.push_str(&formatted);
+            shown.push(*n);
+        }
+
+        let mut result = match (shown.first(), shown.last()) {
+            (Some(first), Some(last)) if *first == offset && char_offset > 0 => format!(
+                "[{}] lines {}-{} of {}, line {} from char {}\n",
+                handle, first, last, total, first, char_offset
+            ),
+            (Some(first), Some(last)) => {
+                format!("[{}] lines {}-{} of {}\n", handle, first, last, total)
+            }
+            _ => format!("[{}] no matching lines ({} lines total)\n", handle, total),
+        };
+        result.push_str(&lines);
+        if let Some((line, next)) = cut {
+            result.push_str(&format!(
+                "[line cut to fit the context; continue with offset {} and char_offset {}]\n",
+                line, next
+            ));
+        } else if shown.len() < selected.len()
+            && let Some(last) = shown.last()
+        {
+            result.push_str(&format!(
+                "[page cut to fit the context; continue with offset {}]\n",
+                last + 1
+            ));
+        }
+        Ok(result)
+    }
+}
+
+// MemoryTool - Saves, updates and deletes long-term memory entries
+pub struct MemoryTool {
+    store: Arc<MemoryStore>,
+}
+
+impl MemoryTool {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryTool {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn description(&self) -> &str {
+        "Manage long-term memory that persists across sessions (project conventions, user preferences)"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "action": {
+                    "type": "string",
+                    "description": "save, update, delete or list"
+                },
+                "content": {
+                    "type": "string",
+                    "description": "The fact to remember (save, update)"
+                },
+                "scope": {
+                    "type": "string",
+                    "description": "project (default) or user"
+                },
+                "id": {
+                    "type": "string",
+                    "description": "Memory id (update, delete)"
+                }
+            },
+            "required": ["action"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let action = args["action"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'action' argument"))?;
+        let content = || {
+            args["content"]
+                .as_str()
+                .filter(|c| !c.trim().is_empty())
+                .ok_or_else(|| anyhow::anyhow!("Missing 'content' argument"))
+        };
+        let id = || {
+            args["id"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("Missing 'id' argument"))
+        };
+
+        match action {
+            "save" => {
+                let scope = MemoryScope::parse(args["scope"].as_str().unwrap_or("project"))?;
+                let entry = self.store.save(scope, content()?)?;
+                Ok(format!("Saved memory [{}]", entry.id))
+            }
+            "update" => {
+                let entry = self.store.update(id()?, content()?)?;
+                Ok(format!("Updated memory [{}]", entry.id))
+            }
+            "delete" => {
+                let entry = self.store.delete(id()?)?;
+                Ok(format!("Deleted memory [{}]", entry.id))
+            }
+            "list" => {
+                let section = self.store.list_section();
+                if section.is_empty() {
+                    Ok("No memories saved".to_string())
+                } else {
+                    Ok(section)
+                }
+            }
+            _ => Err(anyhow::anyhow!("Unknown memory action: {}", action)),
+        }
+    }
+}
+
 // SubAgentTool - Spawns a new subagent to handle a task
 // The actual subagent execution requires SubAgentManager, which should be
 // integrated at the CLI level. This tool provides the interface.
@@ -173,10 +504,128 @@ mod tests {
         assert_eq!(result.unwrap().trim(), "Hello Tool");
     }
 
+    #[tokio::test]
+    async fn test_tool_output_tool() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(SpillStore::new(dir.path().to_path_buf(), 100).unwrap());
+        let handle = store.store("bash", "alpha\nbeta\ngamma\ndelta\n").unwrap();
+        let tool = ToolOutputTool::new(store);
+
+        let page = tool
+            .call(serde_json::json!({"handle": handle, "offset": 2, "limit": 2}))
+            .await
+            .unwrap();
+        assert!(page.contains("lines 2-3 of 4"));
+        assert!(page.contains("beta") && page.contains("gamma"));
+        assert!(!page.contains("alpha") && !page.contains("delta"));
+
+        let grep = tool
+            .call(serde_json::json!({"handle": handle, "pattern": "^d"}))
+            .await
+            .unwrap();
+        assert!(grep.contains("     4  delta"));
+        assert!(!grep.contains("beta"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_output_page_is_not_elided() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(SpillStore::new(dir.path().to_path_buf(), 200).unwrap());
+        let output: String = (1..=100).map(|i| format!("line {}\n", i)).collect();
+        let handle = store.store("bash", &output).unwrap();
+        let mut registry = ToolRegistry::new();
+        registry.set_spill_store(store.clone());
+        let tool = ToolOutputTool::new(store);
+
+        let page = tool
+            .call(serde_json::json!({"handle": handle}))
+            .await
+            .unwrap();
+        assert_eq!(registry.limit_output("tool_output", page.clone()), page);
+        assert!(
+            page.starts_with(&format!("[{}] lines 1-13 of 100\n", handle)),
+            "{}",
+            page
+        );
+        assert!(page.contains("    13  line 13\n"));
+        assert!(page.ends_with("continue with offset 14]\n"));
+
+        let next = tool
+            .call(serde_json::json!({"handle": handle, "offset": 14, "limit": 2}))
+            .await
+            .unwrap();
+        assert_eq!(
+            next,
+            format!(
+                "[{}] lines 14-15 of 100\n    14  line 14\n    15  line 15\n",
+                handle
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tool_output_cuts_long_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(SpillStore::new(dir.path().to_path_buf(), 100).unwrap());
+        let blob: String = (0..250)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+        let handle = store.store("bash", &format!("{}\nnext\n", blob)).unwrap();
+        let tool = ToolOutputTool::new(store);
+
+        let page = tool
+            .call(serde_json::json!({"handle": handle}))
+            .await
+            .unwrap();
+        assert!(page.contains(&format!("     1  {}\n", &blob[..91])));
+        assert!(page.ends_with("continue with offset 1 and char_offset 91]\n"));
+
+        let mut rest = String::new();
+        let mut char_offset = 91;
+        while char_offset < blob.len() {
+            let page = tool
+                .call(serde_json::json!({"handle": handle, "char_offset": char_offset}))
+                .await
+                .unwrap();
+            assert!(page.contains(&format!("line 1 from char {}", char_offset)));
+            let body = page.lines().nth(1).unwrap();
+            assert!(body.chars().count() <= 100);
+            rest.push_str(&body[8..]);
+            char_offset = match page.rsplit_once("char_offset ") {
+                Some((_, next)) => next.trim_end_matches("]\n").parse().unwrap(),
+                None => {
+                    assert!(page.contains("     2  next\n"));
+                    blob.len()
+                }
+            };
+        }
+        assert_eq!(format!("{}{}", &blob[..91], rest), blob);
+    }
+
     #[test]
     fn test_registry() {
         let mut registry = ToolRegistry::new();
         registry.register(Arc::new(BashTool));
         assert!(registry.get("bash").is_some());
     }
+
+    #[test]
+    fn test_generate_prompts() {
+        let mut registry = ToolRegistry::new();
+        registry.register(Arc::new(BashTool));
+
+        let system = registry.generate_system_prompt("");
+        assert!(system.starts_with("You are a helpful coding agent.\n\nWhen you are finished"));
+        assert!(system.ends_with(&registry.generate_tool_instructions()));
+
+        let subagent = registry.generate_subagent_prompt("Code", "fix it");
+        assert!(subagent.starts_with("You are a Code SubAgent"));
+        assert!(subagent.contains("testing.\n\nWhen you are finished"));
+        assert!(subagent.contains("## bash: Execute a bash command"));
+        assert!(
+            registry
+                .generate_subagent_prompt("other", "x")
+                .starts_with("You are a general-purpose SubAgent")
+        );
+    }
 }
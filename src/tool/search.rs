@@ -0,0 +1,364 @@
+use super::Tool;
+use anyhow::Result;
+use async_trait::async_trait;
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::WalkBuilder;
+use regex::RegexBuilder;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+const GLOB_DEFAULT_LIMIT: usize = 100;
+const GREP_DEFAULT_LIMIT: usize = 100;
+// Longer lines in grep results are cut to this many characters
+const MAX_LINE_CHARS: usize = 200;
+// Files above this size are not searched
+const MAX_GREP_FILE_BYTES: u64 = 2 * 1024 * 1024;
+
+// Files under `base` in path order, skipping hidden files and anything
+// excluded by .gitignore, .ignore or the global git excludes
+fn walk_files(base: &Path, max_filesize: Option<u64>) -> impl Iterator<Item = PathBuf> {
+    WalkBuilder::new(base)
+        .require_git(false)
+        .max_filesize(max_filesize)
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+        .map(|entry| entry.into_path())
+}
+
+fn glob_matcher(pattern: &str, case_insensitive: bool) -> Result<GlobMatcher> {
+    Ok(GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .case_insensitive(case_insensitive)
+        .build()
+        .map_err(|e| anyhow::anyhow!("Invalid glob '{}': {}", pattern, e))?
+        .compile_matcher())
+}
+
+// `path` relative to the search root, as shown in results and matched by globs
+fn relative(path: &Path, base: &Path) -> String {
+    path.strip_prefix(base)
+        .ok()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+fn limit_arg(args: &Value, default: usize) -> usize {
+    args["limit"]
+        .as_u64()
+        .map_or(default, |n| n.max(1) as usize)
+}
+
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((i, _)) => format!("{}...", &line[..i]),
+        None => line.to_string(),
+    }
+}
+
+// GlobTool - Finds files by glob pattern
+pub struct GlobTool;
+
+#[async_trait]
+impl Tool for GlobTool {
+    fn name(&self) -> &str {
+        "glob"
+    }
+
+    fn description(&self) -> &str {
+        "Find files by glob pattern (e.g. src/**/*.rs), skipping hidden and .gitignored files"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Glob relative to path; * does not cross directories, ** does"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "Directory to search (default: current directory)"
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Match names ignoring case (default: false)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of files to return (default: 100)"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let pattern = args["pattern"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'pattern' argument"))?;
+        let base = Path::new(args["path"].as_str().unwrap_or("."));
+        if !base.is_dir() {
+            return Err(anyhow::anyhow!("{} is not a directory", base.display()));
+        }
+        let matcher = glob_matcher(pattern, args["case_insensitive"].as_bool().unwrap_or(false))?;
+        let limit = limit_arg(&args, GLOB_DEFAULT_LIMIT);
+
+        let files: Vec<String> = walk_files(base, None)
+            .map(|path| relative(&path, base))
+            .filter(|path| matcher.is_match(path))
+            .collect();
+        if files.is_empty() {
+            return Ok(format!("No files match '{}'", pattern));
+        }
+        let mut result = format!("{} file(s) match '{}'\n", files.len(), pattern);
+        for file in files.iter().take(limit) {
+            result.push_str(file);
+            result.push('\n');
+        }
+        if files.len() > limit {
+            result.push_str(&format!(
+                "... {} more not shown; narrow the pattern or raise limit\n",
+                files.len() - limit
+            ));
+        }
+        Ok(result)
+    }
+}
+
+// GrepTool - Searches file contents by regex
+pub struct GrepTool;
+
+#[async_trait]
+impl Tool for GrepTool {
+    fn name(&self) -> &str {
+        "grep"
+    }
+
+    fn description(&self) -> &str {
+        "Search file contents with a regex, skipping hidden, binary and .gitignored files. Results are grouped by file as `line: text`, with context lines as `line- text`"
+    }
+
+    fn schema(&self) -> Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "pattern": {
+                    "type": "string",
+                    "description": "Regex to search for (Rust regex syntax)"
+                },
+                "path": {
+                    "type": "string",
+                    "description": "File or directory to search (default: current directory)"
+                },
+                "glob": {
+                    "type": "string",
+                    "description": "Only search files whose path matches this glob (e.g. **/*.rs)"
+                },
+                "case_insensitive": {
+                    "type": "boolean",
+                    "description": "Ignore case (default: false)"
+                },
+                "context": {
+                    "type": "integer",
+                    "description": "Lines of context before and after each match (default: 0)"
+                },
+                "files_only": {
+                    "type": "boolean",
+                    "description": "Only list the files that match, with their match counts (default: false)"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of matching lines (or files with files_only) to return (default: 100)"
+                }
+            },
+            "required": ["pattern"]
+        })
+    }
+
+    async fn call(&self, args: Value) -> Result<String> {
+        let pattern = args["pattern"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing 'pattern' argument"))?;
+        let case_insensitive = args["case_insensitive"].as_bool().unwrap_or(false);
+        let re = RegexBuilder::new(pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid regex '{}': {}", pattern, e))?;
+        let base = Path::new(args["path"].as_str().unwrap_or("."));
+        if !base.exists() {
+            return Err(anyhow::anyhow!("{} does not exist", base.display()));
+        }
+        let glob = args["glob"]
+            .as_str()
+            .map(|g| glob_matcher(g, case_insensitive))
+            .transpose()?;
+        let context = args["context"].as_u64().unwrap_or(0) as usize;
+        let files_only = args["files_only"].as_bool().unwrap_or(false);
+        let limit = limit_arg(&args, GREP_DEFAULT_LIMIT);
+
+        let mut body = String::new();
+        let mut shown = 0;
+        let mut total_matches = 0;
+        let mut total_files = 0;
+        for path in walk_files(base, Some(MAX_GREP_FILE_BYTES)) {
+            let name = relative(&path, base);
+            if glob.as_ref().is_some_and(|g| !g.is_match(&name)) {
+                continue;
+            }
+            // Binary and non-UTF-8 files are skipped
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            if content.contains('\0') {
+                continue;
+            }
+            let lines: Vec<&str> = content.lines().collect();
+            let matches: Vec<usize> = (0..lines.len())
+                .filter(|&i| re.is_match(lines[i]))
+                .collect();
+            if matches.is_empty() {
+                continue;
+            }
+            total_files += 1;
+            total_matches += matches.len();
+            if shown >= limit {
+                continue;
+            }
+            if files_only {
+                body.push_str(&format!("{} ({})\n", name, matches.len()));
+                shown += 1;
+                continue;
+            }
+
+            body.push_str(&name);
+            body.push('\n');
+            // Last line printed, to merge overlapping context and mark gaps
+            let mut printed: Option<usize> = None;
+            for &m in &matches {
+                if shown >= limit {
+                    break;
+                }
+                let from = m.saturating_sub(context);
+                let from = printed.map_or(from, |p| from.max(p + 1));
+                if context > 0 && printed.is_some_and(|p| from > p + 1) {
+                    body.push_str("--\n");
+                }
+                let to = (m + context).min(lines.len() - 1);
+                for (i, line) in lines.iter().enumerate().take(to + 1).skip(from) {
+                    let sep = if re.is_match(line) { ':' } else { '-' };
+                    body.push_str(&format!("{}{} {}\n", i + 1, sep, truncate_line(line)));
+                }
+                printed = Some(to);
+                shown += 1;
+            }
+        }
+
+        if total_matches == 0 {
+            return Ok(format!("No matches for '{}'", pattern));
+        }
+        let mut result = format!(
+            "{} match(es) in {} file(s) for '{}'\n{}",
+            total_matches, total_files, pattern, body
+        );
+        let total = if files_only {
+            total_files
+        } else {
+            total_matches
+        };
+        if total > limit {
+            result.push_str(&format!(
+                "... {} more not shown; narrow the pattern or path, or raise limit\n",
+                total - limit
+            ));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_glob_and_grep_tools() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        fs::write(
+            root.join("src/main.rs"),
+            "fn main() {\n    run();\n}\n\nfn run() {}\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/nested/lib.rs"), "pub fn Run() {}\n").unwrap();
+        fs::write(root.join("target/gen.rs"), "fn run() {}\n").unwrap();
+        let path = root.to_str().unwrap();
+
+        let found = GlobTool
+            .call(serde_json::json!({"pattern": "**/*.rs", "path": path}))
+            .await
+            .unwrap();
+        assert_eq!(
+            found,
+            "2 file(s) match '**/*.rs'\nsrc/main.rs\nsrc/nested/lib.rs\n"
+        );
+        let found = GlobTool
+            .call(serde_json::json!({"pattern": "src/*.rs", "path": path}))
+            .await
+            .unwrap();
+        assert!(!found.contains("lib.rs"));
+
+        let grep = GrepTool
+            .call(serde_json::json!({"pattern": r"fn \w+\(", "path": path, "limit": 2}))
+            .await
+            .unwrap();
+        assert!(grep.starts_with("3 match(es) in 2 file(s)"), "{}", grep);
+        assert!(
+            grep.contains("src/main.rs\n1: fn main() {\n5: fn run() {}\n"),
+            "{}",
+            grep
+        );
+        assert!(grep.contains("... 1 more not shown"));
+
+        let grep = GrepTool
+            .call(serde_json::json!({
+                "pattern": "run\\(",
+                "path": path,
+                "case_insensitive": true,
+                "context": 1,
+                "glob": "src/*.rs"
+            }))
+            .await
+            .unwrap();
+        assert!(
+            grep.ends_with(
+                "src/main.rs\n1- fn main() {\n2:     run();\n3- }\n4- \n5: fn run() {}\n"
+            ),
+            "{}",
+            grep
+        );
+
+        let grep = GrepTool
+            .call(serde_json::json!({"pattern": "run", "path": path, "case_insensitive": true, "files_only": true}))
+            .await
+            .unwrap();
+        assert!(
+            grep.ends_with("src/main.rs (2)\nsrc/nested/lib.rs (1)\n"),
+            "{}",
+            grep
+        );
+        assert!(
+            GrepTool
+                .call(serde_json::json!({"pattern": "(", "path": path}))
+                .await
+                .is_err()
+        );
+    }
+}